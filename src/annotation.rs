@@ -0,0 +1,37 @@
+use std::ops::Range;
+
+// 一行中需要特殊显示的部分, 比如搜索结果和语法高亮
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AnnotationType {
+    Match,
+    SelectedMatch,
+    Selection,
+    // 下面是语法高亮用的
+    Comment,
+    Keyword,
+    Type,
+    String,
+    Number,
+    // true, false, null之类的字面量
+    Constant,
+    // Markdown的标题, TOML的表头
+    Heading,
+    Emphasis,
+    // TOML和JSON的键
+    Key,
+    // shell的变量
+    Variable,
+}
+
+#[derive(Clone, Debug)]
+pub struct Annotation {
+    pub annotation_type: AnnotationType,
+    // grapheme的下标范围
+    pub range: Range<usize>,
+}
+
+// 一段样式相同的文本, 没有annotation的部分用默认样式
+pub struct AnnotatedText {
+    pub text: String,
+    pub annotation_type: Option<AnnotationType>,
+}
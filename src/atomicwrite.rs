@@ -0,0 +1,267 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+// 临时文件名被占用时最多换几次后缀
+const MAX_TEMP_ATTEMPTS: usize = 100;
+
+// 把contents安全地写到path:
+// 先写到同一目录下的临时文件并fsync, 复制原文件的权限和所有者, 再rename覆盖原文件
+// 这样写到一半崩溃或者磁盘满了, 原文件也不会被截断
+// 没法rename的情况(比如硬链接, 目录不可写)退回到直接覆盖写
+pub fn write(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    // 符号链接要写到它指向的文件, 而不是把链接本身替换掉
+    let target = resolve_symlink(path);
+    let metadata = fs::metadata(&target).ok();
+    // 指向不存在的文件的链接, 以及有多个硬链接的文件, rename都会把链接弄断
+    let is_dangling_symlink = fs::symlink_metadata(&target).is_ok_and(|metadata| metadata.file_type().is_symlink());
+    if is_dangling_symlink || metadata.as_ref().is_some_and(has_other_links) {
+        return write_in_place(&target, contents);
+    }
+    let (temp_path, mut file) = match create_temp(&target, metadata.as_ref()) {
+        Ok(created) => created,
+        // 目录不可写, 建不了临时文件, 只能直接写原文件
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => return write_in_place(&target, contents),
+        Err(err) => return Err(err),
+    };
+    // 写临时文件失败时原文件还是完好的, 不能再去覆盖它
+    let result = write_temp(&mut file, contents, metadata.as_ref());
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+    if fs::rename(&temp_path, &target).is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return write_in_place(&target, contents);
+    }
+    sync_parent_dir(&target);
+    Ok(())
+}
+
+fn resolve_symlink(path: &Path) -> PathBuf {
+    let is_symlink = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink());
+    if is_symlink {
+        // 链接指向的文件不存在时canonicalize会失败, 这时通过链接直接写
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    } else {
+        path.to_path_buf()
+    }
+}
+
+// 临时文件名里带上进程号, 崩溃的进程可能留下了同名的文件(进程号会被重用), 这时换一个后缀再试
+fn create_temp(target: &Path, metadata: Option<&fs::Metadata>) -> Result<(PathBuf, File), Error> {
+    let mut attempt = 0;
+    loop {
+        let temp_path = temp_path_for(target, attempt)?;
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        set_create_mode(&mut options, metadata);
+        match options.open(&temp_path) {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists && attempt < MAX_TEMP_ATTEMPTS => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+// 先设置好所有者和权限再写内容, 这样只有原文件的读者能看到临时文件里的内容
+// chown成功时会清掉setuid/setgid位, 所以要放在设置权限之前
+fn write_temp(file: &mut File, contents: &[u8], metadata: Option<&fs::Metadata>) -> Result<(), Error> {
+    if let Some(metadata) = metadata {
+        copy_owner(file, metadata);
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+fn write_in_place(target: &Path, contents: &[u8]) -> Result<(), Error> {
+    let mut file = File::create(target)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+// 临时文件放在同一个目录下, 保证rename不会跨文件系统
+fn temp_path_for(target: &Path, attempt: usize) -> Result<PathBuf, Error> {
+    let file_name = target
+        .file_name()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    if attempt == 0 {
+        temp_name.push(format!(".{}.tmp", process::id()));
+    } else {
+        temp_name.push(format!(".{}.{attempt}.tmp", process::id()));
+    }
+    Ok(target.with_file_name(temp_name))
+}
+
+// 创建时就用原文件的权限(还要受umask限制), 不给别人留下读到内容的机会
+#[cfg(unix)]
+fn set_create_mode(options: &mut OpenOptions, metadata: Option<&fs::Metadata>) {
+    use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+    if let Some(metadata) = metadata {
+        options.mode(metadata.mode() & 0o777);
+    }
+}
+
+#[cfg(not(unix))]
+fn set_create_mode(_options: &mut OpenOptions, _metadata: Option<&fs::Metadata>) {}
+
+#[cfg(unix)]
+fn has_other_links(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn has_other_links(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+// 只有root才能随意修改所有者, 失败了也没关系
+#[cfg(unix)]
+fn copy_owner(file: &File, metadata: &fs::Metadata) {
+    use std::os::unix::fs::{fchown, MetadataExt};
+    let _ = fchown(file, Some(metadata.uid()), Some(metadata.gid()));
+}
+
+#[cfg(not(unix))]
+fn copy_owner(_file: &File, _metadata: &fs::Metadata) {}
+
+// rename之后fsync目录, 确保目录项也落盘了
+#[cfg(unix)]
+fn sync_parent_dir(target: &Path) {
+    let parent = match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_target: &Path) {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::{symlink, MetadataExt, PermissionsExt};
+    use std::path::PathBuf;
+    use std::process;
+
+    use super::{temp_path_for, write};
+
+    // 每个测试用自己的目录, 结束时删掉
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("rs-editor-atomicwrite-{}-{name}", process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::set_permissions(&self.0, fs::Permissions::from_mode(0o755));
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // 目录里所有文件的名字, 用来检查临时文件都清理掉了
+    fn only_entries(dir: &TestDir) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(&dir.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn keeps_permissions() {
+        let dir = TestDir::new("permissions");
+        let path = dir.0.join("secret.txt");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        let inode = fs::metadata(&path).unwrap().ino();
+        write(&path, b"new").unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.mode() & 0o777, 0o600);
+        // 是通过rename换掉的, 不是直接覆盖写
+        assert_ne!(metadata.ino(), inode);
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(only_entries(&dir), ["secret.txt"]);
+    }
+
+    #[test]
+    fn writes_through_symlink() {
+        let dir = TestDir::new("symlink");
+        let target = dir.0.join("target.txt");
+        let link = dir.0.join("link.txt");
+        fs::write(&target, "old").unwrap();
+        symlink(&target, &link).unwrap();
+        write(&link, b"new").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert_eq!(only_entries(&dir), ["link.txt", "target.txt"]);
+    }
+
+    #[test]
+    fn keeps_hardlinks() {
+        let dir = TestDir::new("hardlink");
+        let path = dir.0.join("a.txt");
+        let other = dir.0.join("b.txt");
+        fs::write(&path, "old").unwrap();
+        fs::hard_link(&path, &other).unwrap();
+        write(&path, b"new").unwrap();
+        assert_eq!(fs::read(&other).unwrap(), b"new");
+        assert_eq!(fs::metadata(&path).unwrap().ino(), fs::metadata(&other).unwrap().ino());
+    }
+
+    #[test]
+    fn stale_temp_file_gets_a_new_name() {
+        let dir = TestDir::new("stale");
+        let path = dir.0.join("file.txt");
+        fs::write(&path, "old").unwrap();
+        let stale = temp_path_for(&path, 0).unwrap();
+        fs::write(&stale, "left by a crash").unwrap();
+        let inode = fs::metadata(&path).unwrap().ino();
+        write(&path, b"new").unwrap();
+        assert_ne!(fs::metadata(&path).unwrap().ino(), inode);
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read(&stale).unwrap(), b"left by a crash");
+    }
+
+    #[test]
+    fn falls_back_to_writing_in_place_when_directory_is_read_only() {
+        let dir = TestDir::new("readonly");
+        let path = dir.0.join("file.txt");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&dir.0, fs::Permissions::from_mode(0o555)).unwrap();
+        // root不受目录权限的限制, 这时测不到这种情况, 硬链接和悬空的链接也会走同一条路
+        if fs::write(dir.0.join("probe"), "").is_ok() {
+            return;
+        }
+        let inode = fs::metadata(&path).unwrap().ino();
+        write(&path, b"new").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().ino(), inode);
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+    }
+
+    #[test]
+    fn dangling_symlink_is_written_in_place() {
+        let dir = TestDir::new("dangling");
+        let target = dir.0.join("missing.txt");
+        let link = dir.0.join("link.txt");
+        symlink(&target, &link).unwrap();
+        write(&link, b"new").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+    }
+}
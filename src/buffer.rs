@@ -1,16 +1,79 @@
 use std::fs;
-use std::fs::File;
-use std::io::Error;
+use std::fs::OpenOptions;
+use std::io::{Error, ErrorKind};
+use std::mem;
+use std::path::{Path, PathBuf};
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::annotation::Annotation;
 use crate::line::Line;
 use crate::location::Location;
-use std::io::Write;
-use crate::fileinfo::FileInfo;
+use crate::atomicwrite;
+use crate::encoding::Encoding;
+use crate::fileinfo::{FileInfo, LineEnding};
+use crate::highlighter::Highlighter;
+use crate::history::{Edit, History};
+use crate::syntax::Language;
+
+// 正则表达式在buffer中的一个匹配
+pub struct RegexMatch {
+    pub start: Location,
+    pub end: Location,
+    // 按照替换模板展开之后的文本
+    pub replacement: String,
+}
+
+// 一次修改: [start, old_end)之间的文本换成了[start, new_end)之间的文本
+// undo/redo只知道换掉了哪几行, 范围是整行
+#[derive(Copy, Clone)]
+pub struct TextChange {
+    start: Location,
+    old_end: Location,
+    new_end: Location,
+}
+
+impl TextChange {
+    // 修改之前的位置在修改之后对应到哪里
+    // 修改范围之前的不动, 之后的跟着平移, 在被换掉的文本里的尽量留在原处
+    pub fn shift(&self, location: Location) -> Location {
+        if location <= self.start {
+            location
+        } else if location < self.old_end {
+            location.min(self.new_end)
+        } else if location.line_index == self.old_end.line_index {
+            Location {
+                line_index: self.new_end.line_index,
+                grapheme_index: location.grapheme_index - self.old_end.grapheme_index + self.new_end.grapheme_index,
+            }
+        } else {
+            Location {
+                line_index: location.line_index - self.old_end.line_index + self.new_end.line_index,
+                ..location
+            }
+        }
+    }
+
+    // 整行的修改: 从line_index开始的removed行换成了inserted行
+    const fn lines(line_index: usize, removed: usize, inserted: usize) -> Self {
+        Self {
+            start: Location { line_index, grapheme_index: 0 },
+            old_end: Location { line_index: line_index + removed, grapheme_index: 0 },
+            new_end: Location { line_index: line_index + inserted, grapheme_index: 0 },
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct Buffer {
     pub lines: Vec<Line>,
     pub file_info: FileInfo,
     pub dirty: bool,
+    history: History,
+    highlighter: Highlighter,
+    // 内容每变一次加一, 显示这个buffer的view用它判断是不是要跟着更新
+    revision: usize,
+    // 上次take_changes之后的修改, 按照发生的顺序
+    changes: Vec<TextChange>,
 }
 
 impl Buffer {
@@ -19,18 +82,94 @@ impl Buffer {
     }
 
     pub fn load(filename: &str) -> Result<Self, Error> {
-        let contents = fs::read_to_string(filename)?;
+        Self::load_with_encoding(filename, None)
+    }
+
+    // encoding为None时自动检测文件的编码
+    pub fn load_with_encoding(filename: &str, encoding: Option<Encoding>) -> Result<Self, Error> {
+        let bytes = fs::read(filename)?;
+        let mut file_info = FileInfo::from(filename);
+        file_info.encoding = encoding.unwrap_or_else(|| Encoding::detect(&bytes));
+        // 二进制文件逐字节解码, 这样每个字节都能原样显示出来, 不会被替换掉
+        if encoding.is_none() && Self::looks_binary(&bytes, file_info.encoding) {
+            file_info.encoding = Encoding::Latin1;
+            file_info.binary = true;
+        }
+        file_info.read_only = file_info.binary || !Self::is_writable(filename);
+        // 记下BOM, 换行符和最后一行有没有换行, 保存时原样写回去
+        let (contents, has_bom) = file_info.encoding.decode(&bytes)?;
+        file_info.has_bom = has_bom;
+        let contents = contents.as_str();
+        // 换行符混用时按多数来, 保存时统一成这一种
+        let endings = contents.matches('\n').count();
+        let crlf_endings = contents.matches("\r\n").count();
+        if crlf_endings > 0 && crlf_endings * 2 >= endings {
+            file_info.line_ending = LineEnding::Crlf;
+        }
+        file_info.trailing_newline = contents.is_empty() || contents.ends_with('\n');
+
         let mut lines = Vec::new();
-        for value in contents.lines() {
+        for (index, value) in contents.split_terminator('\n').enumerate() {
+            // 每一行末尾的\r都是换行符的一部分, 没有换行的最后一行除外
+            let has_newline = index < endings;
+            let value = if has_newline { value.strip_suffix('\r').unwrap_or(value) } else { value };
             lines.push(Line::from(value));
         }
+        let mut highlighter = Highlighter::default();
+        highlighter.set_language(file_info.language());
         Ok(Self {
             lines,
-            file_info: FileInfo::from(filename),
+            file_info,
             dirty: false,
+            history: History::default(),
+            highlighter,
+            revision: 0,
+            changes: Vec::new(),
         })
     }
 
+    // 有NUL字节(UTF-16除外), 或者只能当成Latin-1却含有空白以外的C0控制字符的, 都当成二进制文件
+    // 0x80到0x9F在Windows-1252里是引号和€之类的常见字符, 不能算
+    fn looks_binary(bytes: &[u8], encoding: Encoding) -> bool {
+        match encoding {
+            Encoding::Utf16Le | Encoding::Utf16Be => false,
+            Encoding::Latin1 => bytes
+                .iter()
+                .any(|&byte| byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0B | 0x0C)),
+            _ => bytes.contains(&0),
+        }
+    }
+
+    fn is_writable(filename: &str) -> bool {
+        OpenOptions::new().append(true).open(filename).is_ok()
+    }
+
+    // 用指定的编码重新读取文件, 内容整个换掉了, 没有修改记录
+    pub fn reload_with_encoding(&mut self, filename: &str, encoding: Encoding) -> Result<(), Error> {
+        let revision = self.revision;
+        *self = Self::load_with_encoding(filename, Some(encoding))?;
+        self.revision = revision.wrapping_add(1);
+        Ok(())
+    }
+
+    pub const fn revision(&self) -> usize {
+        self.revision
+    }
+
+    // 取出上次调用之后的修改
+    pub fn take_changes(&mut self) -> Vec<TextChange> {
+        mem::take(&mut self.changes)
+    }
+
+    fn record_change(&mut self, change: TextChange) {
+        self.revision = self.revision.wrapping_add(1);
+        self.changes.push(change);
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.file_info.read_only
+    }
+
     pub fn height(&self) -> usize {
         self.lines.len()
     }
@@ -39,19 +178,12 @@ impl Buffer {
         if at.line_index > self.lines.len() {
             return;
         }
-        // 在最后一行插入
-        if at.line_index == self.lines.len() {
-            self.lines.push(Line::from(character.to_string().as_str()));
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_index) {
-            // 一行的中间插入
-            line.insert_char(character, at.grapheme_index);
-            self.dirty = true;
-        }
+        // 在最后一行插入 或者 一行的中间插入
+        self.replace_range(at, at, &character.to_string(), at, true);
     }
 
-    pub fn delete(&mut self, at: Location) {
-        // 这里没法get_mut
+    // 删除at处的一个字符, caret是删除前光标的位置, undo之后光标回到那里
+    pub fn delete(&mut self, at: Location, caret: Location) {
         if let Some(line) = self.lines.get(at.line_index) {
             // 光标在行首 按一下 backspace之后的状态:
             // 光标在末尾 并且 不是在最后一行
@@ -59,37 +191,513 @@ impl Buffer {
                 && self.lines.len() > at.line_index.saturating_add(1)
             {
                 // 把下一行合并到当前行
-                let next_line = self.lines.remove(at.line_index.saturating_add(1));
-                self.lines[at.line_index].append(&next_line);
-                self.dirty = true;
+                let next = Location {
+                    line_index: at.line_index.saturating_add(1),
+                    grapheme_index: 0,
+                };
+                self.replace_range(at, next, "", caret, false);
             } else if at.grapheme_index < line.grapheme_count() {
-                self.lines[at.line_index].delete(at.grapheme_index);
-                self.dirty = true;
+                let next = Location {
+                    line_index: at.line_index,
+                    grapheme_index: at.grapheme_index.saturating_add(1),
+                };
+                self.replace_range(at, next, "", caret, false);
             }
         }
     }
 
-    pub fn insert_newline(&mut self, at: Location) {
-        if at.line_index == self.height() {
-            // 末尾插入一行
-            self.lines.push(Line::default());
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_index) {
-            // 把剩下的部分插到下一行
-            let new = line.split(at.grapheme_index);
-            self.lines.insert(at.line_index.saturating_add(1), new);
+    // 从from开始向后查找query, 到末尾之后从头继续, 返回第一个匹配的开头
+    pub fn search_forward(&self, query: &str, from: Location) -> Option<Location> {
+        let height = self.height();
+        if height == 0 {
+            return None;
+        }
+        let from = if from.line_index >= height {
+            Location::default()
+        } else {
+            from
+        };
+        // 多找一次是为了兜底: 回到起始行, 看看from前面有没有匹配
+        for offset in 0..=height {
+            let line_index = (from.line_index + offset) % height;
+            let matches = self.lines[line_index].find_all(query);
+            let found = if offset == 0 {
+                matches.iter().find(|range| range.start >= from.grapheme_index)
+            } else if offset == height {
+                matches.iter().find(|range| range.start < from.grapheme_index)
+            } else {
+                matches.first()
+            };
+            if let Some(range) = found {
+                return Some(Location {
+                    line_index,
+                    grapheme_index: range.start,
+                });
+            }
+        }
+        None
+    }
+
+    // 从from开始向前查找query, 到开头之后从末尾继续, 返回找到的匹配的开头
+    pub fn search_backward(&self, query: &str, from: Location) -> Option<Location> {
+        let height = self.height();
+        if height == 0 {
+            return None;
+        }
+        let from = if from.line_index >= height {
+            Location {
+                line_index: height - 1,
+                grapheme_index: self.lines[height - 1].grapheme_count(),
+            }
+        } else {
+            from
+        };
+        for offset in 0..=height {
+            let line_index = (from.line_index + height - offset % height) % height;
+            let matches = self.lines[line_index].find_all(query);
+            let found = if offset == 0 {
+                matches.iter().rev().find(|range| range.start < from.grapheme_index)
+            } else if offset == height {
+                matches.iter().rev().find(|range| range.start >= from.grapheme_index)
+            } else {
+                matches.last()
+            };
+            if let Some(range) = found {
+                return Some(Location {
+                    line_index,
+                    grapheme_index: range.start,
+                });
+            }
+        }
+        None
+    }
+
+    // 修改保存时使用的换行符, 内容没变但也算是修改
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.file_info.line_ending != line_ending {
+            self.file_info.line_ending = line_ending;
+            self.history.forget_saved();
             self.dirty = true;
         }
     }
 
+    // 撤销上一次修改, 返回修改前光标的位置
+    pub fn undo(&mut self) -> Option<Location> {
+        let edits = self.history.undo()?;
+        for edit in edits.iter().rev() {
+            Self::splice(&mut self.lines, &mut self.highlighter, edit.line_index, edit.after.len(), &edit.before);
+            self.changes.push(TextChange::lines(edit.line_index, edit.after.len(), edit.before.len()));
+        }
+        let caret = edits.first().map(|edit| edit.caret_before);
+        self.revision = self.revision.wrapping_add(1);
+        self.dirty = !self.history.is_at_saved();
+        caret
+    }
+
+    // 重做被撤销的修改, 返回修改后光标的位置
+    pub fn redo(&mut self) -> Option<Location> {
+        let edits = self.history.redo()?;
+        for edit in edits {
+            Self::splice(&mut self.lines, &mut self.highlighter, edit.line_index, edit.before.len(), &edit.after);
+            self.changes.push(TextChange::lines(edit.line_index, edit.before.len(), edit.after.len()));
+        }
+        let caret = edits.last().map(|edit| edit.caret_after);
+        self.revision = self.revision.wrapping_add(1);
+        self.dirty = !self.history.is_at_saved();
+        caret
+    }
+
+    // 在begin_transaction和end_transaction之间的修改会被当成一次修改来undo
+    pub fn begin_transaction(&mut self) {
+        self.history.begin_group();
+    }
+
+    pub fn end_transaction(&mut self) {
+        self.history.end_group();
+    }
+
+    // 从from开始向后查找正则表达式的匹配, 不会回到开头
+    // 匹配必须在同一行内, 并且开头和结尾都要落在grapheme边界上
+    pub fn find_regex(&self, regex: &Regex, template: &str, from: Location) -> Option<RegexMatch> {
+        for line_index in from.line_index..self.height() {
+            let line = &self.lines[line_index];
+            let text = line.to_string();
+            let offsets = line.byte_offsets();
+            let mut start_byte = if line_index == from.line_index {
+                match offsets.get(from.grapheme_index) {
+                    Some(&offset) => offset,
+                    None => continue,
+                }
+            } else {
+                0
+            };
+            while let Some(captures) = regex.captures_at(&text, start_byte) {
+                let Some(found) = captures.get(0) else {
+                    break;
+                };
+                if let (Ok(start), Ok(end)) = (
+                    offsets.binary_search(&found.start()),
+                    offsets.binary_search(&found.end()),
+                ) {
+                    let mut replacement = String::new();
+                    captures.expand(template, &mut replacement);
+                    return Some(RegexMatch {
+                        start: Location { line_index, grapheme_index: start },
+                        end: Location { line_index, grapheme_index: end },
+                        replacement,
+                    });
+                }
+                // 匹配落在了grapheme中间, 从下一个grapheme开始重新找
+                match offsets.iter().find(|&&offset| offset > found.start()) {
+                    Some(&offset) => start_byte = offset,
+                    None => break,
+                }
+            }
+        }
+        None
+    }
+
+    // 取出[start, end)之间的文本, 行与行之间用'\n'连接
+    pub fn text_range(&self, start: Location, end: Location) -> String {
+        let mut text = String::new();
+        for (line_index, line) in self.lines.iter().enumerate().skip(start.line_index) {
+            let from = if line_index == start.line_index { start.grapheme_index } else { 0 };
+            if line_index == end.line_index {
+                text.push_str(&line.substr(from..end.grapheme_index));
+                break;
+            }
+            text.push_str(&line.substr(from..line.grapheme_count()));
+            text.push('\n');
+        }
+        text
+    }
+
+    // 把[start, end)之间的文本替换成text, 返回text末尾的位置
+    // caret是修改前光标的位置, undo之后光标回到那里
+    pub fn replace(&mut self, start: Location, end: Location, text: &str, caret: Location) -> Location {
+        self.replace_range(start, end, text, caret, false)
+    }
+
+    // 把[start, end)之间的文本替换成text, 并记录到history中
+    // line_index == height() 表示最后一行之后的那个空行, 在那里插入会追加新的行
+    // 返回插入的文本末尾的位置
+    fn replace_range(&mut self, start: Location, end: Location, text: &str, caret: Location, mergeable: bool) -> Location {
+        let height = self.height();
+        let (prefix, suffix, remove) = if start.line_index >= height {
+            (String::new(), None, 0)
+        } else if end.line_index >= height {
+            let line = &self.lines[start.line_index];
+            (line.substr(0..start.grapheme_index), None, height - start.line_index)
+        } else {
+            let first = &self.lines[start.line_index];
+            let last = &self.lines[end.line_index];
+            (
+                first.substr(0..start.grapheme_index),
+                Some(last.substr(end.grapheme_index..last.grapheme_count())),
+                end.line_index - start.line_index + 1,
+            )
+        };
+
+        let mut combined = prefix;
+        combined.push_str(text);
+        let suffix_len = suffix.as_ref().map_or(0, |suffix| suffix.graphemes(true).count());
+        let after: Vec<String> = match &suffix {
+            Some(suffix) => {
+                combined.push_str(suffix);
+                combined.split('\n').map(String::from).collect()
+            }
+            // 文件末尾的换行是隐含的, 所以最后一个换行不会产生新的空行
+            None => combined.split_terminator('\n').map(String::from).collect(),
+        };
+        let line_index = start.line_index.min(height);
+        let before: Vec<String> = self.lines[line_index..line_index + remove]
+            .iter()
+            .map(Line::to_string)
+            .collect();
+
+        let caret_line = line_index + text.matches('\n').count();
+        let caret_after = Location {
+            line_index: caret_line,
+            grapheme_index: after
+                .get(caret_line - line_index)
+                .map_or(0, |line| line.graphemes(true).count().saturating_sub(suffix_len)),
+        };
+
+        // 超出最后一行的位置都是最后的空行
+        let clamp = |location: Location| {
+            if location.line_index >= height {
+                Location { line_index: height, grapheme_index: 0 }
+            } else {
+                location
+            }
+        };
+        let change = TextChange {
+            start: clamp(start),
+            old_end: clamp(end),
+            new_end: caret_after,
+        };
+        Self::splice(&mut self.lines, &mut self.highlighter, line_index, before.len(), &after);
+        self.record_change(change);
+        self.history.push(
+            Edit {
+                line_index,
+                before,
+                after,
+                caret_before: caret,
+                caret_after,
+            },
+            mergeable,
+        );
+        self.dirty = true;
+        caret_after
+    }
+
+    fn splice(lines: &mut Vec<Line>, highlighter: &mut Highlighter, line_index: usize, remove: usize, insert: &[String]) {
+        lines.splice(
+            line_index..line_index + remove,
+            insert.iter().map(|line| Line::from(line.as_str())),
+        );
+        highlighter.splice(line_index, remove, insert.len());
+    }
+
+    // 高亮前until行, 没有修改过的行会复用之前的结果
+    pub fn update_highlighting(&mut self, until: usize) {
+        self.highlighter.update(&self.lines, until);
+    }
+
+    pub fn syntax_annotations(&self, line_index: usize) -> &[Annotation] {
+        self.highlighter.annotations(line_index)
+    }
+
+    pub fn language(&self) -> Option<Language> {
+        self.highlighter.language()
+    }
+
     pub fn save(&mut self) -> Result<(), Error> {
-        if let Some(filename) = &self.file_info.path {
-            let mut file = File::create(filename)?;
-            for line in &self.lines {
-                writeln!(file, "{line}")?;
+        let Some(path) = self.file_info.path.clone() else {
+            return Err(Error::other("no file name"));
+        };
+        if self.file_info.read_only {
+            return Err(Error::new(ErrorKind::PermissionDenied, "file is read-only"));
+        }
+        self.save_to(&path)
+    }
+
+    // 保存到filename, 成功之后这个buffer就对应到新的文件
+    pub fn save_as(&mut self, filename: &str) -> Result<(), Error> {
+        let path = PathBuf::from(filename);
+        self.save_to(&path)?;
+        self.file_info.path = Some(path);
+        // 另存为之后就是一个可以写的新文件了
+        self.file_info.read_only = false;
+        // 扩展名可能变了, 重新选择语言
+        self.highlighter.set_language(self.file_info.language());
+        Ok(())
+    }
+
+    fn save_to(&mut self, path: &Path) -> Result<(), Error> {
+        if self.file_info.binary {
+            return Err(Error::new(ErrorKind::PermissionDenied, "binary files are read-only"));
+        }
+        let line_ending = self.file_info.line_ending.as_str();
+        let mut contents = String::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            contents.push_str(&line.to_string());
+            if index + 1 < self.lines.len() || self.file_info.trailing_newline {
+                contents.push_str(line_ending);
             }
-            self.dirty = false;
         }
+        let bytes = self.file_info.encoding.encode(&contents, self.file_info.has_bom)?;
+        atomicwrite::write(path, &bytes)?;
+        self.history.mark_saved();
+        self.dirty = false;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    use super::Buffer;
+    use crate::encoding::Encoding;
+    use crate::fileinfo::LineEnding;
+    use crate::line::Line;
+    use crate::location::Location;
+
+    fn buffer(text: &str) -> Buffer {
+        Buffer {
+            lines: text.lines().map(Line::from).collect(),
+            ..Buffer::default()
+        }
+    }
+
+    fn text(buffer: &Buffer) -> Vec<String> {
+        buffer.lines.iter().map(Line::to_string).collect()
+    }
+
+    const fn at(line_index: usize, grapheme_index: usize) -> Location {
+        Location { line_index, grapheme_index }
+    }
+
+    #[test]
+    fn consecutive_inserts_undo_together() {
+        let mut buffer = buffer("");
+        for (index, ch) in "abc".chars().enumerate() {
+            buffer.insert_char(ch, at(0, index));
+        }
+        assert_eq!(text(&buffer), ["abc"]);
+        assert_eq!(buffer.undo(), Some(at(0, 0)));
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.redo(), Some(at(0, 3)));
+        assert_eq!(text(&buffer), ["abc"]);
+    }
+
+    #[test]
+    fn transaction_is_one_undo_step() {
+        let mut buffer = buffer("one\ntwo");
+        buffer.begin_transaction();
+        buffer.replace(at(0, 0), at(0, 0), "  ", at(1, 1));
+        buffer.replace(at(1, 0), at(1, 0), "  ", at(1, 1));
+        buffer.end_transaction();
+        assert_eq!(text(&buffer), ["  one", "  two"]);
+        assert_eq!(buffer.undo(), Some(at(1, 1)));
+        assert_eq!(text(&buffer), ["one", "two"]);
+        assert_eq!(buffer.undo(), None);
+    }
+
+    #[test]
+    fn undo_restores_caret_before_forward_delete() {
+        let mut buffer = buffer("abc");
+        buffer.delete(at(0, 1), at(0, 1));
+        assert_eq!(text(&buffer), ["ac"]);
+        assert_eq!(buffer.undo(), Some(at(0, 1)));
+        assert_eq!(text(&buffer), ["abc"]);
+    }
+
+    #[test]
+    fn undo_restores_caret_before_backspace() {
+        let mut buffer = buffer("abc");
+        // 光标在c前面按backspace, 删掉的是b
+        buffer.delete(at(0, 1), at(0, 2));
+        assert_eq!(buffer.undo(), Some(at(0, 2)));
+    }
+
+    #[test]
+    fn undo_restores_caret_of_backward_selection() {
+        let mut buffer = buffer("hello world");
+        // 从后往前选中了"hello ", 光标在开头
+        buffer.replace(at(0, 0), at(0, 6), "", at(0, 0));
+        assert_eq!(text(&buffer), ["world"]);
+        assert_eq!(buffer.undo(), Some(at(0, 0)));
+    }
+
+    #[test]
+    fn undo_to_saved_state_is_clean() {
+        let mut buffer = buffer("abc");
+        buffer.insert_char('x', at(0, 3));
+        buffer.history.mark_saved();
+        buffer.dirty = false;
+        buffer.insert_char(' ', at(0, 4));
+        buffer.insert_char('y', at(0, 5));
+        assert!(buffer.dirty);
+        buffer.undo();
+        assert_eq!(text(&buffer), ["abcx"]);
+        assert!(!buffer.dirty);
+        buffer.undo();
+        assert!(buffer.dirty);
+        buffer.redo();
+        assert!(!buffer.dirty);
+    }
+
+    #[test]
+    fn new_edit_after_undoing_past_save_loses_saved_state() {
+        let mut buffer = buffer("abc");
+        buffer.insert_char('x', at(0, 3));
+        buffer.history.mark_saved();
+        buffer.dirty = false;
+        buffer.undo();
+        assert!(buffer.dirty);
+        // 新的修改让redo失效, 保存时的状态再也回不去了
+        buffer.insert_char('y', at(0, 3));
+        buffer.undo();
+        assert_eq!(text(&buffer), ["abc"]);
+        assert!(buffer.dirty);
+        assert_eq!(buffer.redo(), Some(at(0, 4)));
+        assert_eq!(text(&buffer), ["abcy"]);
+        assert!(buffer.dirty);
+    }
+
+    // 写到临时文件里读进来, 再原样保存一遍, 返回读到的buffer和保存之后的内容
+    fn round_trip(name: &str, contents: &[u8]) -> (Buffer, Vec<u8>) {
+        let path: PathBuf = std::env::temp_dir().join(format!("rs-editor-buffer-{}-{name}", process::id()));
+        fs::write(&path, contents).unwrap();
+        let mut buffer = Buffer::load(path.to_str().unwrap()).unwrap();
+        buffer.save().unwrap();
+        let saved = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        (buffer, saved)
+    }
+
+    #[test]
+    fn crlf_round_trip() {
+        let (buffer, saved) = round_trip("crlf", b"a\r\nb\r\n");
+        assert_eq!(text(&buffer), ["a", "b"]);
+        assert_eq!(buffer.file_info.line_ending, LineEnding::Crlf);
+        assert_eq!(saved, b"a\r\nb\r\n");
+    }
+
+    #[test]
+    fn bom_round_trip() {
+        let (buffer, saved) = round_trip("bom", b"\xEF\xBB\xBFa\nb\n");
+        assert_eq!(text(&buffer), ["a", "b"]);
+        assert!(buffer.file_info.has_bom);
+        assert_eq!(saved, b"\xEF\xBB\xBFa\nb\n");
+    }
+
+    #[test]
+    fn missing_final_newline_round_trip() {
+        let (buffer, saved) = round_trip("no-newline", b"a\r\nb");
+        assert_eq!(text(&buffer), ["a", "b"]);
+        assert!(!buffer.file_info.trailing_newline);
+        assert_eq!(saved, b"a\r\nb");
+    }
+
+    #[test]
+    fn mixed_endings_follow_the_majority() {
+        // 第一行是LF, 其余是CRLF
+        let (buffer, saved) = round_trip("mixed-crlf", b"a\nb\r\nc\r\n");
+        assert_eq!(text(&buffer), ["a", "b", "c"]);
+        assert_eq!(buffer.file_info.line_ending, LineEnding::Crlf);
+        assert_eq!(saved, b"a\r\nb\r\nc\r\n");
+
+        let (mut buffer, saved) = round_trip("mixed-lf", b"a\nb\nc\r\n");
+        assert_eq!(text(&buffer), ["a", "b", "c"]);
+        assert_eq!(buffer.file_info.line_ending, LineEnding::Lf);
+        assert_eq!(saved, b"a\nb\nc\n");
+        // 换成CRLF之后不会出现\r\r\n
+        buffer.set_line_ending(LineEnding::Crlf);
+        let path = std::env::temp_dir().join(format!("rs-editor-buffer-{}-mixed-save", process::id()));
+        buffer.save_as(path.to_str().unwrap()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"a\r\nb\r\nc\r\n");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn control_bytes_make_latin1_binary() {
+        assert!(Buffer::looks_binary(b"abc\x00def", Encoding::Latin1));
+        assert!(Buffer::looks_binary(b"\x7FELF\x02\x01", Encoding::Latin1));
+        assert!(Buffer::looks_binary(b"\x1B[0m caf\xE9", Encoding::Latin1));
+        assert!(Buffer::looks_binary(b"abc\x00", Encoding::Utf8));
+    }
+
+    #[test]
+    fn windows_1252_text_is_not_binary() {
+        // 弯引号, €和破折号都在0x80到0x9F之间
+        assert!(!Buffer::looks_binary(b"\x93quoted\x94 costs \x80 5 \x96 ok\r\n", Encoding::Latin1));
+        assert!(!Buffer::looks_binary(b"tab\there\x0Cpage\x0B\n", Encoding::Latin1));
+        assert!(!Buffer::looks_binary(b"\xFF\xFEa\x00", Encoding::Utf16Le));
+    }
+}
@@ -0,0 +1,125 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::buffer::Buffer;
+use crate::location::Location;
+use crate::terminal::Position;
+use crate::view::View;
+
+// 一个打开的buffer, 连同最后一次离开它时的光标位置和滚动位置, 切换回来时恢复
+#[derive(Clone, Default)]
+pub struct SavedBuffer {
+    pub buffer: Rc<RefCell<Buffer>>,
+    pub location: Location,
+    pub scroll_offset: Position,
+}
+
+// 打开的所有buffer, 按照打开的顺序排列
+// 每个窗口显示其中的一个, 几个窗口也可以显示同一个
+pub struct BufferList {
+    entries: Vec<SavedBuffer>,
+}
+
+impl BufferList {
+    // view里现在显示的buffer作为第一个
+    pub fn new(view: &View) -> Self {
+        Self {
+            entries: vec![SavedBuffer {
+                buffer: Rc::clone(view.buffer()),
+                ..SavedBuffer::default()
+            }],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // view里显示的是第几个buffer
+    pub fn index_of(&self, view: &View) -> Option<usize> {
+        self.entries.iter().position(|entry| Rc::ptr_eq(&entry.buffer, view.buffer()))
+    }
+
+    // 每个buffer的路径和是否有未保存的修改
+    pub fn summaries(&self) -> Vec<(String, bool)> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let buffer = entry.buffer.borrow();
+                let name = buffer.file_info.path.as_ref()
+                    .map_or_else(|| String::from("[No Name]"), |path| path.display().to_string());
+                (name, buffer.dirty)
+            })
+            .collect()
+    }
+
+    // 已经打开了同一个文件的buffer
+    pub fn find(&self, path: &Path) -> Option<usize> {
+        let target = fs::canonicalize(path).ok()?;
+        self.entries.iter().position(|entry| {
+            entry.buffer.borrow().file_info.path.as_ref()
+                .and_then(|path| fs::canonicalize(path).ok())
+                .is_some_and(|path| path == target)
+        })
+    }
+
+    // 第一个有未保存修改的buffer
+    pub fn first_modified(&self) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.buffer.borrow().dirty)
+    }
+
+    // 加到最后并在view里显示
+    // view里是一个没有动过, 也没有在别的窗口里显示的空buffer时(比如启动时没有指定文件), 直接替换掉它
+    pub fn add(&mut self, view: &mut View, buffer: Buffer) {
+        let current = view.buffer();
+        let untouched = {
+            let buffer = current.borrow();
+            buffer.file_info.path.is_none() && !buffer.dirty && buffer.is_empty()
+        };
+        // 一个引用在entries里, 一个在view里
+        if untouched && Rc::strong_count(current) == 2 {
+            if let Some(index) = self.index_of(view) {
+                self.entries.remove(index);
+            }
+        }
+        self.entries.push(SavedBuffer {
+            buffer: Rc::new(RefCell::new(buffer)),
+            ..SavedBuffer::default()
+        });
+        self.switch_to(view, self.entries.len() - 1);
+    }
+
+    // 在view里显示第index个buffer, 记下view离开的buffer的位置
+    pub fn switch_to(&mut self, view: &mut View, index: usize) {
+        if index >= self.entries.len() || self.index_of(view) == Some(index) {
+            return;
+        }
+        let previous = view.swap_buffer(self.entries[index].clone());
+        if let Some(entry) = self.entries.iter_mut().find(|entry| Rc::ptr_eq(&entry.buffer, &previous.buffer)) {
+            *entry = previous;
+        }
+    }
+
+    pub fn next(&mut self, view: &mut View) {
+        let index = self.index_of(view).map_or(0, |index| (index + 1) % self.entries.len());
+        self.switch_to(view, index);
+    }
+
+    pub fn previous(&mut self, view: &mut View) {
+        let len = self.entries.len();
+        let index = self.index_of(view).map_or(0, |index| (index + len - 1) % len);
+        self.switch_to(view, index);
+    }
+
+    // 从列表中去掉第index个buffer, 返回它和显示它的窗口应该换成的buffer
+    // 换成它后面的一个, 关闭的是最后一个时换成前面的一个, 一个都不剩时换成一个新的空buffer
+    pub fn close(&mut self, index: usize) -> (Rc<RefCell<Buffer>>, usize) {
+        let closed = self.entries.remove(index).buffer;
+        if self.entries.is_empty() {
+            self.entries.push(SavedBuffer::default());
+        }
+        (closed, index.min(self.entries.len() - 1))
+    }
+}
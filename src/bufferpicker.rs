@@ -0,0 +1,99 @@
+use crate::screen::Screen;
+use crate::style::StyledText;
+use crate::terminal::{Position, Size};
+use crate::theme::Theme;
+use crate::uicomponent::UIComponent;
+
+// 列出所有打开的buffer, 盖在正文区域的底部, 选中一个之后切换过去
+#[derive(Default)]
+pub struct BufferPicker {
+    // 每个buffer的路径和是否有未保存的修改
+    entries: Vec<(String, bool)>,
+    selected: usize,
+    visible: bool,
+    needs_redraw: bool,
+    // 正文区域的大小, 列表最多占满整个正文区域
+    size: Size,
+    theme: Theme,
+}
+
+impl BufferPicker {
+    pub fn show(&mut self, entries: Vec<(String, bool)>, selected: usize) {
+        self.entries = entries;
+        self.selected = selected;
+        self.visible = true;
+        self.mark_redraw(true);
+    }
+
+    pub fn hide(&mut self) {
+        self.visible = false;
+        self.entries.clear();
+    }
+
+    pub const fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+            self.mark_redraw(true);
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        if self.selected > 0 {
+            self.selected -= 1;
+            self.mark_redraw(true);
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.mark_redraw(true);
+    }
+}
+
+impl UIComponent for BufferPicker {
+    fn mark_redraw(&mut self, value: bool) {
+        self.needs_redraw = value;
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    // 第一行是标题, 下面每行一个buffer, 放不下时滚动到选中的那一个
+    fn draw(&mut self, origin: Position, screen: &mut Screen) {
+        if !self.visible || self.size.height == 0 {
+            return;
+        }
+        let rows = self.entries.len().min(self.size.height.saturating_sub(1));
+        let top = origin.row + self.size.height - rows - 1;
+        let width = self.size.width;
+        let header = self.theme.status_bar;
+        let at = |row| Position { row, col: origin.col };
+        screen.print_styled(at(top), width, header, &[StyledText::new(" Buffers", header)]);
+        let first = (self.selected + 1).saturating_sub(rows);
+        let number_width = self.entries.len().to_string().len();
+        for (row, index) in (first..first + rows).enumerate() {
+            let (name, modified) = &self.entries[index];
+            let modified = if *modified { " [+]" } else { "" };
+            let style = if index == self.selected {
+                self.theme.on_text(self.theme.selection)
+            } else {
+                self.theme.text
+            };
+            let text = format!(" {:>number_width$}  {name}{modified}", index + 1);
+            screen.print_styled(at(top + 1 + row), width, style, &[StyledText::new(text, style)]);
+        }
+    }
+}
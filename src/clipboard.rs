@@ -0,0 +1,177 @@
+use std::fmt;
+use std::io::{Error, ErrorKind, Write};
+use std::process::{Command as Process, Stdio};
+
+use crossterm::Command;
+use serde::Deserialize;
+
+use crate::terminal::Backend;
+
+// 复制/剪切的内容
+#[derive(Default, Clone)]
+pub struct Register {
+    pub text: String,
+    // 没有选区时复制的是整行, 粘贴时要插到当前行的上面
+    pub linewise: bool,
+}
+
+// 复制的内容还要同步到哪里, 在配置文件里用 clipboard = "osc52" 这样的形式选择
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClipboardBackend {
+    // 只用编辑器内部的寄存器
+    #[default]
+    Internal,
+    // 通过终端的OSC 52转义序列写到终端所在机器的剪贴板, ssh上也能用
+    Osc52,
+    Xclip,
+    WlCopy,
+    Pbcopy,
+}
+
+impl ClipboardBackend {
+    // 外部程序的复制和粘贴命令
+    fn commands(self) -> Option<(&'static [&'static str], &'static [&'static str])> {
+        match self {
+            Self::Internal | Self::Osc52 => None,
+            Self::Xclip => Some((
+                &["xclip", "-selection", "clipboard", "-in"],
+                &["xclip", "-selection", "clipboard", "-out"],
+            )),
+            Self::WlCopy => Some((&["wl-copy"], &["wl-paste", "--no-newline"])),
+            Self::Pbcopy => Some((&["pbcopy"], &["pbpaste"])),
+        }
+    }
+}
+
+// 内部寄存器总是会保存一份, 外部剪贴板不可用时就用它
+#[derive(Default)]
+pub struct Clipboard {
+    backend: ClipboardBackend,
+    register: Register,
+}
+
+impl Clipboard {
+    pub fn new(backend: ClipboardBackend) -> Self {
+        Self {
+            backend,
+            register: Register::default(),
+        }
+    }
+
+    // 内部寄存器一定会更新, 同步到系统剪贴板失败时返回错误
+    // OSC 52要通过terminal发给终端
+    pub fn copy(&mut self, register: Register, terminal: &mut dyn Backend) -> Result<(), Error> {
+        self.register = register;
+        let text = &self.register.text;
+        match self.backend.commands() {
+            Some((copy, _)) => run_copy(copy, text),
+            None if self.backend == ClipboardBackend::Osc52 => terminal.set_clipboard(text),
+            None => Ok(()),
+        }
+    }
+
+    // 优先读取系统剪贴板, 读不到时用内部寄存器
+    pub fn paste(&self) -> Register {
+        let Some((_, paste)) = self.backend.commands() else {
+            return self.register.clone();
+        };
+        match run_paste(paste) {
+            // 内容没有变, 说明是自己复制的, 保留整行的标记
+            Ok(text) if text == self.register.text => self.register.clone(),
+            Ok(text) => Register { text, linewise: false },
+            Err(_) => self.register.clone(),
+        }
+    }
+}
+
+fn run_copy(command: &[&str], text: &str) -> Result<(), Error> {
+    let mut child = Process::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(format!("{} exited with {status}", command[0])))
+    }
+}
+
+fn run_paste(command: &[&str]) -> Result<String, Error> {
+    let output = Process::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::other(format!("{} exited with {}", command[0], output.status)));
+    }
+    String::from_utf8(output.stdout).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+}
+
+// 设置剪贴板的OSC 52序列: ESC ] 52 ; c ; <base64> BEL
+pub struct SetClipboard<'a>(pub &'a str);
+
+impl Command for SetClipboard<'_> {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        write!(f, "\x1b]52;c;{}\x07", base64_encode(self.0.as_bytes()))
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// 标准的base64编码, 末尾用'='补齐
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b0 = chunk[0];
+        let b1 = chunk.get(1).copied().unwrap_or(0);
+        let b2 = chunk.get(2).copied().unwrap_or(0);
+        let indices = [b0 >> 2, (b0 & 0x03) << 4 | b1 >> 4, (b1 & 0x0F) << 2 | b2 >> 6, b2 & 0x3F];
+        for (i, index) in indices.into_iter().enumerate() {
+            // 一个字节只能编出2个字符, 两个字节只能编出3个字符
+            if i <= chunk.len() {
+                encoded.push(char::from(BASE64_ALPHABET[usize::from(index)]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::Command;
+
+    use super::SetClipboard;
+
+    fn escape_sequence(text: &str) -> String {
+        let mut output = String::new();
+        SetClipboard(text).write_ansi(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn set_clipboard_emits_osc52() {
+        assert_eq!(escape_sequence("abcdef"), "\x1b]52;c;YWJjZGVm\x07");
+    }
+
+    #[test]
+    fn set_clipboard_pads_partial_groups() {
+        assert_eq!(escape_sequence(""), "\x1b]52;c;\x07");
+        assert_eq!(escape_sequence("abcd"), "\x1b]52;c;YWJjZA==\x07");
+        assert_eq!(escape_sequence("abcde"), "\x1b]52;c;YWJjZGU=\x07");
+    }
+
+    #[test]
+    fn set_clipboard_encodes_utf8_bytes() {
+        assert_eq!(escape_sequence("héllo 世界"), "\x1b]52;c;aMOpbGxvIOS4lueVjA==\x07");
+    }
+}
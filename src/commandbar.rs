@@ -0,0 +1,130 @@
+
+use crate::editorcommand::{Direction, EditorCommand};
+use crate::line::{Line, TabStops};
+use crate::screen::Screen;
+use crate::terminal::{Position, Size};
+use crate::uicomponent::UIComponent;
+
+// 最后一行的输入框, 比如搜索时输入要查找的内容, 由MessageBar在提示模式下使用
+#[derive(Default)]
+pub struct CommandBar {
+    prompt: String,
+    value: Line,
+    // 光标在value中的grapheme下标
+    caret_index: usize,
+    needs_redraw: bool,
+    size: Size,
+}
+
+impl CommandBar {
+    pub fn set_prompt(&mut self, prompt: &str) {
+        self.prompt = prompt.to_string();
+        self.mark_redraw(true);
+    }
+
+    pub fn value(&self) -> String {
+        self.value.to_string()
+    }
+
+    pub fn clear_value(&mut self) {
+        self.set_value("");
+    }
+
+    // 替换输入框的内容, 光标放到末尾
+    pub fn set_value(&mut self, value: &str) {
+        self.value = Line::from(value);
+        self.caret_index = self.value.grapheme_count();
+        self.mark_redraw(true);
+    }
+
+    // 处理输入框里的编辑命令, 其他命令忽略
+    pub fn handle_command(&mut self, cmd: EditorCommand) {
+        let count = self.value.grapheme_count();
+        match cmd {
+            EditorCommand::Insert(ch) => self.insert(&ch.to_string()),
+            EditorCommand::Indent => self.insert("\t"),
+            // 输入框只有一行, 只取粘贴内容的第一行
+            EditorCommand::PasteText(text) => self.insert(text.lines().next().unwrap_or_default()),
+            EditorCommand::Backspace if self.caret_index > 0 => {
+                self.caret_index -= 1;
+                self.delete();
+            }
+            EditorCommand::Delete => self.delete(),
+            EditorCommand::Move(Direction::Left) => {
+                self.caret_index = self.caret_index.saturating_sub(1);
+            }
+            EditorCommand::Move(Direction::Right) => {
+                self.caret_index = count.min(self.caret_index.saturating_add(1));
+            }
+            EditorCommand::Move(Direction::Home) => self.caret_index = 0,
+            EditorCommand::Move(Direction::End) => self.caret_index = count,
+            _ => return,
+        }
+        self.mark_redraw(true);
+    }
+
+    fn insert(&mut self, inserted: &str) {
+        let count = self.value.grapheme_count();
+        let mut text = self.value.substr(0..self.caret_index);
+        text.push_str(inserted);
+        text.push_str(&self.value.substr(self.caret_index..count));
+        self.value = Line::from(text.as_str());
+        // 组合字符可能会和前一个grapheme合并, 这时光标不需要移动
+        let new_count = self.value.grapheme_count();
+        self.caret_index = self.caret_index.saturating_add(new_count.saturating_sub(count));
+    }
+
+    fn delete(&mut self) {
+        let count = self.value.grapheme_count();
+        if self.caret_index < count {
+            let mut text = self.value.substr(0..self.caret_index);
+            text.push_str(&self.value.substr(self.caret_index.saturating_add(1)..count));
+            self.value = Line::from(text.as_str());
+        }
+    }
+
+    // 光标所在的列
+    pub fn caret_position_col(&self) -> usize {
+        let caret_col = self
+            .prompt
+            .len()
+            .saturating_add(self.value.width_until(self.caret_index, TabStops::default()))
+            .saturating_sub(self.scroll_start());
+        caret_col.min(self.size.width.saturating_sub(1))
+    }
+
+    // 输入的内容放不下的时候, 只显示光标附近的部分, 返回value开始显示的列
+    fn scroll_start(&self) -> usize {
+        let area_for_value = self.size.width.saturating_sub(self.prompt.len());
+        self.value
+            .width_until(self.caret_index, TabStops::default())
+            .saturating_sub(area_for_value.saturating_sub(1))
+    }
+}
+
+impl UIComponent for CommandBar {
+    fn mark_redraw(&mut self, value: bool) {
+        self.needs_redraw = value;
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    fn draw(&mut self, origin: Position, screen: &mut Screen) {
+        let area_for_value = self.size.width.saturating_sub(self.prompt.len());
+        let start = self.scroll_start();
+        let value = self.value.get_visible_graphemes(start..start.saturating_add(area_for_value), TabStops::default());
+        // 提示太长的时候截断, 至少让用户看到开头
+        let to_print = if self.prompt.len() <= self.size.width {
+            format!("{}{value}", self.prompt)
+        } else {
+            self.prompt.chars().take(self.size.width).collect()
+        };
+        screen.print_row(origin.row, &to_print);
+    }
+}
@@ -0,0 +1,85 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::clipboard::ClipboardBackend;
+use crate::editor::NAME;
+use crate::gutter::LineNumbers;
+use crate::indent::Indentation;
+use crate::line::TabStops;
+
+// 用户的配置, 从 ~/.config/rs-editor/config.toml 读取, 没有配置的项使用默认值
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // 有未保存的修改时, 需要连续按几次Ctrl-Q才能直接退出
+    pub quit_times: usize,
+    // 复制的内容同步到哪个剪贴板: internal, osc52, xclip, wl-copy, pbcopy
+    pub clipboard: ClipboardBackend,
+    // 行号的显示方式: off, absolute, relative, hybrid
+    pub line_numbers: LineNumbers,
+    // 制表位的宽度
+    pub tab_width: usize,
+    // 是否把制表符显示成箭头
+    pub show_tabs: bool,
+    // 按Tab时插入空格
+    pub expand_tabs: bool,
+    // 主题的名字或者主题文件的路径, 没有配置时使用默认主题
+    pub theme: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            quit_times: 3,
+            clipboard: ClipboardBackend::default(),
+            line_numbers: LineNumbers::default(),
+            tab_width: 4,
+            show_tabs: false,
+            expand_tabs: false,
+            theme: None,
+        }
+    }
+}
+
+impl Config {
+    // 配置文件不存在时使用默认配置, 格式错误时返回错误信息
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Ok(Self::default());
+        };
+        toml::from_str(&contents).map_err(|err| format!("{}: {}", path.display(), err.message()))
+    }
+
+    pub fn tab_stops(&self) -> TabStops {
+        TabStops {
+            width: self.tab_width.max(1),
+            visible: self.show_tabs,
+        }
+    }
+
+    pub fn indentation(&self) -> Indentation {
+        Indentation {
+            width: self.tab_width.max(1),
+            expand_tabs: self.expand_tabs,
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        Some(Self::dir()?.join("config.toml"))
+    }
+
+    // 配置目录, 优先使用 $XDG_CONFIG_HOME, 其次是 $HOME/.config
+    pub fn dir() -> Option<PathBuf> {
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join(NAME))
+    }
+}
@@ -1,9 +1,18 @@
+use crate::encoding::Encoding;
+use crate::fileinfo::LineEnding;
+use crate::syntax::Language;
+
 #[derive(Default, Eq, PartialEq, Debug)]
 pub struct DocumentStatus {
     pub total_lines: usize,
     pub current_line_index: usize,
     pub is_modified: bool,
     pub filename: String,
+    pub line_ending: LineEnding,
+    pub encoding: Encoding,
+    pub is_read_only: bool,
+    pub is_binary: bool,
+    pub language: Option<Language>,
 }
 
 impl DocumentStatus {
@@ -15,10 +24,30 @@ impl DocumentStatus {
         }
     }
 
+    pub fn read_only_indicator_to_string(&self) -> String {
+        if self.is_read_only {
+            String::from(" [RO]")
+        } else {
+            String::new()
+        }
+    }
+
     pub fn line_count_to_string(&self) -> String {
         format!("{} lines", self.total_lines)
     }
 
+    pub fn file_format_to_string(&self) -> String {
+        let format = if self.is_binary {
+            format!("binary | {}", self.line_ending)
+        } else {
+            format!("{} | {}", self.encoding, self.line_ending)
+        };
+        match self.language {
+            Some(language) => format!("{language} | {format}"),
+            None => format,
+        }
+    }
+
     pub fn position_indicator_to_string(&self) -> String {
         format!(
             "{}/{}",
@@ -1,24 +1,105 @@
 use std::env;
-use std::io::{Error, Read};
+use std::io::Error;
+use std::mem;
 use std::panic::{set_hook, take_hook};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use crossterm::event::{Event, KeyEvent, KeyEventKind, read};
+use crossterm::event::{Event, KeyEvent, KeyEventKind, poll, read};
+use regex::Regex;
 
-use crate::editorcommand::EditorCommand;
-use crate::statusbar::StatusBar;
-use crate::terminal::{Size, Terminal};
+use crate::buffer::Buffer;
+use crate::bufferlist::BufferList;
+use crate::bufferpicker::BufferPicker;
+use crate::clipboard::{Clipboard, Register};
+use crate::config::Config;
+use crate::encoding::Encoding;
+use crate::editorcommand::{Direction, EditorCommand};
+use crate::layout::{self, Layout, Rect, SplitDirection, WindowId};
+use crate::messagebar::{MessageBar, PromptResult};
+use crate::screen::Screen;
+use crate::style::{ColorSupport, StyledText};
+use crate::terminal::{Backend, Position, Size, Terminal};
+use crate::theme::Theme;
 use crate::view::View;
 use crate::uicomponent::UIComponent;
+use crate::window::Window;
 
 pub const NAME: &str = env!("CARGO_PKG_NAME");
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+// 两次点击间隔多久以内算双击
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+const HELP_MESSAGE: &str = "HELP: Ctrl-F = find | Ctrl-R = replace | Ctrl-S = save | Ctrl-B = buffers | Ctrl-Q = quit";
 
-#[derive(Default)]
-pub struct Editor {
+// 当前MessageBar的输入框是用来做什么的
+#[derive(Default, Eq, PartialEq, Copy, Clone)]
+enum PromptType {
+    Search,
+    // 输入要替换的正则表达式
+    ReplaceFind,
+    // 输入替换成什么
+    ReplaceWith,
+    // 逐个确认替换
+    ReplaceConfirm,
+    // 输入另存为的文件名
+    SaveAs,
+    // 有未保存的修改时确认是否退出
+    QuitConfirm,
+    // 输入重新打开文件时使用的编码
+    Encoding,
+    // 输入要打开的文件名
+    Open,
+    // 关闭有未保存修改的buffer时确认
+    CloseConfirm,
+    // 在buffer列表中选择要切换到的buffer
+    BufferList,
+    #[default]
+    None,
+}
+
+// 保存成功之后接着做什么
+#[derive(Default, Eq, PartialEq, Copy, Clone)]
+enum AfterSave {
+    #[default]
+    Nothing,
+    Quit,
+    CloseBuffer,
+}
+
+// B是画面输出到的终端, 默认是真实的终端
+pub struct Editor<B: Backend = Terminal> {
+    terminal: B,
     should_quit: bool,
-    view: View,
-    status_bar: StatusBar,
+    // 所有的窗口, 按照打开的顺序排列, focus是有输入焦点的那一个
+    windows: Vec<Window>,
+    focus: usize,
+    // 窗口在屏幕上怎么排列
+    layout: Layout,
+    next_window_id: WindowId,
+    // 左右排列的窗口之间的竖线
+    separators: Vec<Rect>,
+    // 所有打开的buffer, 每个窗口显示其中的一个
+    buffers: BufferList,
+    buffer_picker: BufferPicker,
+    theme: Theme,
+    message_bar: MessageBar,
+    prompt_type: PromptType,
+    // 输入替换模板时, 保存上一步输入的正则表达式
+    replace_regex: Option<Regex>,
+    config: Config,
+    // 连续按了几次Ctrl-Q
+    quit_presses: usize,
+    // 选择了保存并退出/关闭, 可能还要先输入文件名
+    after_save: AfterSave,
+    // 剪切/复制的内容
+    clipboard: Clipboard,
+    // 上一次鼠标点击的时间和位置, 用来判断双击
+    last_click: Option<(Instant, Position)>,
     terminal_size: Size,
+    // 组件画在screen上, previous是上一次输出到终端的画面, 两者比较之后只输出变化的部分
+    screen: Screen,
+    previous: Screen,
     title: String,
 }
 
@@ -28,26 +109,103 @@ impl Editor {
         let current_hook = take_hook();
         // 设置新的回调
         set_hook(Box::new(move |panic_info| {
-            let _ = Terminal::terminate();
+            let _ = Terminal.terminate();
             current_hook(panic_info);
         }));
-        Terminal::initialize()?;
+        let mut this = Self::with_backend(Terminal)?;
+        this.load_config();
+        this.handle_args();
+        this.refresh_status();
+        Ok(this)
+    }
+}
 
-        let mut this = Self::default();
-        let size = Terminal::size().unwrap_or_default();
+impl<B: Backend> Editor<B> {
+    // 在任意的终端上创建编辑器, 使用默认配置, 也不打开命令行参数里的文件
+    pub fn with_backend(mut terminal: B) -> Result<Self, Error> {
+        terminal.initialize()?;
+        let size = terminal.size().unwrap_or_default();
+        let view = View::default();
+        let buffers = BufferList::new(&view);
+        let mut this = Self {
+            terminal,
+            should_quit: false,
+            windows: vec![Window::new(0, view, Theme::default())],
+            focus: 0,
+            layout: Layout::new(0),
+            next_window_id: 1,
+            separators: Vec::new(),
+            buffers,
+            buffer_picker: BufferPicker::default(),
+            theme: Theme::default(),
+            message_bar: MessageBar::default(),
+            prompt_type: PromptType::default(),
+            replace_regex: None,
+            config: Config::default(),
+            quit_presses: 0,
+            after_save: AfterSave::Nothing,
+            clipboard: Clipboard::default(),
+            last_click: None,
+            terminal_size: Size::default(),
+            screen: Screen::default(),
+            previous: Screen::default(),
+            title: String::new(),
+        };
         this.resize(size);
-
-
-        this.handle_args();
+        this.message_bar.update_message(HELP_MESSAGE);
         this.refresh_status();
         Ok(this)
     }
 
+    // 测试时用来检查画面
+    #[cfg(test)]
+    pub const fn terminal(&self) -> &B {
+        &self.terminal
+    }
+
+    fn load_config(&mut self) {
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(err) => {
+                self.message_bar.update_message(&format!("Invalid config: {err}"));
+                return;
+            }
+        };
+        self.clipboard = Clipboard::new(config.clipboard);
+        let theme = Theme::load(config.theme.as_deref(), ColorSupport::detect());
+        for window in &mut self.windows {
+            window.view.set_line_numbers(config.line_numbers);
+            window.view.set_tabs(config.tab_stops());
+            window.view.set_indentation(config.indentation());
+            if let Ok(theme) = &theme {
+                window.set_theme(theme.clone());
+            }
+        }
+        match theme {
+            Ok(theme) => {
+                self.buffer_picker.set_theme(theme.clone());
+                self.theme = theme;
+            }
+            Err(err) => self.message_bar.update_message(&format!("Invalid theme: {err}")),
+        }
+        self.config = config;
+    }
+
+    fn view(&self) -> &View {
+        &self.windows[self.focus].view
+    }
+
+    fn view_mut(&mut self) -> &mut View {
+        &mut self.windows[self.focus].view
+    }
+
+    // 更新每个窗口的状态栏, 标题显示有焦点的窗口里的文件
     pub fn refresh_status(&mut self) {
-        let status = self.view.get_status();
-        let title = format!("{} - {NAME}", status.filename);
-        self.status_bar.update_status(status);
-        if title != self.title && matches!(Terminal::set_title(&title), Ok(())) {
+        for (index, window) in self.windows.iter_mut().enumerate() {
+            window.update_status(index == self.focus);
+        }
+        let title = format!("{} - {NAME}", self.view().get_status().filename);
+        if title != self.title && matches!(self.terminal.set_title(&title), Ok(())) {
             self.title = title;
         }
     }
@@ -58,6 +216,12 @@ impl Editor {
             if self.should_quit {
                 break;
             }
+            // 有消息要过期时不能一直阻塞, 超时之后重绘一次把消息清掉
+            if let Some(timeout) = self.message_bar.time_until_expiry() {
+                if !matches!(poll(timeout), Ok(true)) {
+                    continue;
+                }
+            }
             match read() {
                 Ok(event) => self.evaluate_event(event),
                 Err(err) => {
@@ -68,48 +232,314 @@ impl Editor {
                     }
                 }
             }
-            let status = self.view.get_status();
-            self.status_bar.update_status(status);
         }
     }
 
     fn resize(&mut self, size: Size) {
         self.terminal_size = size;
+        // 改变大小之后终端上原来的内容不可靠了, 清屏之后全部重画
+        self.screen.resize(size);
+        self.previous = Screen::new(size);
+        let _ = self.terminal.clear_screen();
 
-        self.view.resize(Size {
-            // 空出两行
-            height: size.height.saturating_sub(2),
-            width: size.width,
-        });
+        self.buffer_picker.resize(self.window_area().size);
+        self.arrange_windows();
 
-        self.status_bar.resize(Size {
+        self.message_bar.resize(Size {
             height: 1,
             width: size.width,
         });
     }
 
+    // 除了最下面的消息栏, 整个屏幕都用来放窗口
+    const fn window_area(&self) -> Rect {
+        Rect {
+            origin: Position { row: 0, col: 0 },
+            size: Size {
+                height: self.terminal_size.height.saturating_sub(1),
+                width: self.terminal_size.width,
+            },
+        }
+    }
+
+    // 按照布局重新安排每个窗口的位置和大小
+    fn arrange_windows(&mut self) {
+        let (areas, separators) = self.layout.arrange(self.window_area());
+        for (id, area) in areas {
+            if let Some(window) = self.windows.iter_mut().find(|window| window.id == id) {
+                window.set_area(area);
+            }
+        }
+        self.separators = separators;
+    }
+
+    fn focus_window(&mut self, index: usize) {
+        if index != self.focus && index < self.windows.len() {
+            self.focus = index;
+            self.refresh_status();
+        }
+    }
+
+    // 新窗口显示同一个buffer, 出现在下面或者右边, 并获得焦点
+    fn split_window(&mut self, direction: SplitDirection) {
+        let area = self.windows[self.focus].area();
+        let enough_room = match direction {
+            // 每个窗口至少要有一行正文和一行状态栏
+            SplitDirection::Horizontal => area.size.height >= 4,
+            SplitDirection::Vertical => area.size.width >= 3,
+        };
+        if !enough_room {
+            self.message_bar.update_message("Not enough room to split");
+            return;
+        }
+        let id = self.next_window_id;
+        self.next_window_id += 1;
+        self.layout.split(self.windows[self.focus].id, id, direction);
+        let view = self.view().split();
+        self.windows.push(Window::new(id, view, self.theme.clone()));
+        self.arrange_windows();
+        self.focus_window(self.windows.len() - 1);
+    }
+
+    // 只关闭窗口, 它显示的buffer仍然打开着
+    fn close_window(&mut self) {
+        let Some(next) = self.layout.remove(self.windows[self.focus].id) else {
+            self.message_bar.update_message("Cannot close the last window");
+            return;
+        };
+        self.windows.remove(self.focus);
+        self.focus = self.windows.iter().position(|window| window.id == next).unwrap_or(0);
+        self.arrange_windows();
+        self.refresh_status();
+    }
+
+    fn move_focus(&mut self, dir: Direction) {
+        let areas: Vec<(WindowId, Rect)> = self.windows.iter().map(|window| (window.id, window.area())).collect();
+        let from = self.windows[self.focus].area();
+        if let Some(id) = layout::neighbor(&areas, from, dir) {
+            let index = self.windows.iter().position(|window| window.id == id).unwrap_or(self.focus);
+            self.focus_window(index);
+        }
+    }
+
+    // 上下键调整高度, 左右键调整宽度
+    fn resize_window(&mut self, dir: Direction) {
+        let (direction, delta) = match dir {
+            Direction::Up => (SplitDirection::Horizontal, -1),
+            Direction::Down => (SplitDirection::Horizontal, 1),
+            Direction::Left => (SplitDirection::Vertical, -1),
+            Direction::Right => (SplitDirection::Vertical, 1),
+            _ => return,
+        };
+        let area = self.window_area();
+        if self.layout.resize(self.windows[self.focus].id, direction, delta, area) {
+            self.arrange_windows();
+        }
+    }
+
+    // 鼠标点击的位置是相对于整个屏幕的, 转换成相对于窗口的位置, 点在别的窗口上时把焦点移过去
+    // 点在状态栏, 分隔线和消息栏上的不处理
+    // 滚轮滚动鼠标下面的窗口, 不移动焦点
+    // 返回处理这个命令的窗口和转换之后的命令
+    fn route_mouse(&mut self, cmd: EditorCommand) -> Option<(usize, EditorCommand)> {
+        let window_at = |position| self.windows.iter().position(|window: &Window| window.area().contains(position));
+        match cmd {
+            EditorCommand::Click(position) | EditorCommand::DoubleClick(position) => {
+                let index = window_at(position)?;
+                self.focus_window(index);
+                let position = self.windows[index].view_position(position)?;
+                Some((
+                    index,
+                    if matches!(cmd, EditorCommand::Click(_)) {
+                        EditorCommand::Click(position)
+                    } else {
+                        EditorCommand::DoubleClick(position)
+                    },
+                ))
+            }
+            // 拖到窗口外面时, 按照窗口的边界处理
+            EditorCommand::Drag(position) => Some((
+                self.focus,
+                EditorCommand::Drag(position.saturating_sub(self.windows[self.focus].area().origin)),
+            )),
+            EditorCommand::Scroll(dir, position) => {
+                let index = window_at(position)?;
+                Some((index, EditorCommand::Scroll(dir, position.saturating_sub(self.windows[index].area().origin))))
+            }
+            _ => Some((self.focus, cmd)),
+        }
+    }
+
+    // 有焦点的窗口里的buffer可能被修改了, 显示同一个buffer的其他窗口也要跟着更新
+    // 内容没有变的时候它们不用动, 也不用重画
+    fn sync_windows(&mut self) {
+        let buffer = Rc::clone(self.view().buffer());
+        let changes = buffer.borrow_mut().take_changes();
+        for (index, window) in self.windows.iter_mut().enumerate() {
+            if index == self.focus {
+                window.view.mark_synced();
+            } else if Rc::ptr_eq(window.view.buffer(), &buffer) {
+                window.view.sync_with_buffer(&changes);
+            }
+        }
+        self.refresh_status();
+    }
+
+    // 打开命令行上的每一个文件, 显示第一个
     fn handle_args(&mut self) {
-        let args: Vec<String> = env::args().collect();
-        if let Some(filename) = args.get(1) {
-            self.view.load(filename);
+        for filename in env::args().skip(1) {
+            self.open(&filename);
+        }
+        self.buffers.switch_to(&mut self.windows[self.focus].view, 0);
+        self.refresh_status();
+    }
+
+    // 已经打开过的文件直接切换过去, 否则在新的buffer中打开
+    pub fn open(&mut self, filename: &str) {
+        if let Some(index) = self.buffers.find(Path::new(filename)) {
+            self.switch_buffer(index);
+            return;
+        }
+        match Buffer::load(filename) {
+            Ok(buffer) => self.buffers.add(&mut self.windows[self.focus].view, buffer),
+            Err(err) => self.message_bar.update_message(&format!("Could not open {filename}: {err}")),
         }
+        self.refresh_status();
+    }
+
+    fn switch_buffer(&mut self, index: usize) {
+        self.buffers.switch_to(&mut self.windows[self.focus].view, index);
+        self.show_current_buffer();
+    }
+
+    // 切换之后在消息栏显示现在是第几个buffer
+    fn show_current_buffer(&mut self) {
+        self.refresh_status();
+        let index = self.buffers.index_of(self.view()).unwrap_or(0);
+        let message = format!("Buffer {}/{}: {}", index + 1, self.buffers.len(), self.view().get_status().filename);
+        self.message_bar.update_message(&message);
+    }
+
+    fn list_buffers(&mut self) {
+        let current = self.buffers.index_of(self.view()).unwrap_or(0);
+        self.buffer_picker.show(self.buffers.summaries(), current);
+        self.start_prompt(PromptType::BufferList, "Switch to buffer (Up/Down, Enter, 1-9, Esc): ");
     }
 
-    fn evaluate_event(&mut self, event: Event) {
+    // 列表盖住了窗口, 关掉之后窗口要重画
+    fn hide_buffer_picker(&mut self) {
+        self.buffer_picker.hide();
+        for window in &mut self.windows {
+            window.mark_redraw(true);
+        }
+    }
+
+    // 有未保存的修改时先确认
+    fn handle_close_buffer(&mut self) {
+        if self.view().get_status().is_modified {
+            self.start_prompt(
+                PromptType::CloseConfirm,
+                "Unsaved changes! (s)ave & close, (d)iscard, (c)ancel",
+            );
+        } else {
+            self.close_buffer();
+        }
+    }
+
+    // 所有显示这个buffer的窗口都换成另一个buffer
+    fn close_buffer(&mut self) {
+        let Some(index) = self.buffers.index_of(self.view()) else {
+            return;
+        };
+        let (closed, replacement) = self.buffers.close(index);
+        for window in &mut self.windows {
+            if Rc::ptr_eq(window.view.buffer(), &closed) {
+                self.buffers.switch_to(&mut window.view, replacement);
+            }
+        }
+        self.show_current_buffer();
+    }
+
+    // 处理一个终端事件, 处理完之后调用refresh_screen才会显示出来
+    pub fn evaluate_event(&mut self, event: Event) {
         let should_process = match &event {
             Event::Key(KeyEvent { kind, .. }) => kind == &KeyEventKind::Press,
-            Event::Resize(_, _) => true,
+            Event::Resize(_, _) | Event::Paste(_) | Event::Mouse(_) => true,
             _ => false,
         };
 
         if should_process {
             if let Ok(cmd) = EditorCommand::try_from(event) {
-                if matches!(cmd, EditorCommand::Quit) {
-                    self.should_quit = true;
-                } else if let EditorCommand::Resize(size) = cmd {
-                    self.resize(size);
-                } else {
-                    self.view.handle_command(cmd);
+                let cmd = self.detect_double_click(cmd);
+                // 中间按了别的键, 重新开始数Ctrl-Q
+                if !matches!(cmd, EditorCommand::Quit | EditorCommand::Resize(_)) {
+                    self.quit_presses = 0;
+                }
+                match cmd {
+                    EditorCommand::Quit => self.handle_quit(),
+                    EditorCommand::Resize(size) => self.resize(size),
+                    // 输入框打开时, 命令都交给输入框处理
+                    _ if self.prompt_type != PromptType::None => self.process_prompt_command(cmd),
+                    _ if cmd.is_edit() && self.view().is_read_only() => {
+                        self.message_bar.update_message("Buffer is read-only");
+                    }
+                    EditorCommand::Click(_)
+                    | EditorCommand::DoubleClick(_)
+                    | EditorCommand::Drag(_)
+                    | EditorCommand::Scroll(..) => {
+                        if let Some((index, cmd)) = self.route_mouse(cmd) {
+                            self.windows[index].view.handle_command(cmd);
+                        }
+                    }
+                    EditorCommand::Save => self.save(),
+                    EditorCommand::SaveAs => self.start_prompt(PromptType::SaveAs, "Save as: "),
+                    EditorCommand::ToggleLineEnding => {
+                        let line_ending = self.view_mut().toggle_line_ending();
+                        self.message_bar.update_message(&format!("Line endings converted to {line_ending}"));
+                    }
+                    EditorCommand::ToggleSoftWrap => {
+                        let state = if self.view_mut().toggle_soft_wrap() { "on" } else { "off" };
+                        self.message_bar.update_message(&format!("Soft wrap {state}"));
+                    }
+                    EditorCommand::CycleLineNumbers => {
+                        let line_numbers = self.view_mut().cycle_line_numbers();
+                        self.message_bar.update_message(&format!("Line numbers: {line_numbers}"));
+                    }
+                    EditorCommand::ReopenWithEncoding => {
+                        self.start_prompt(PromptType::Encoding, "Reopen with encoding: ");
+                    }
+                    EditorCommand::Copy => {
+                        if let Some(register) = self.view_mut().copy() {
+                            self.copy_to_clipboard(register);
+                        }
+                    }
+                    EditorCommand::Cut => {
+                        if let Some(register) = self.view_mut().cut() {
+                            self.copy_to_clipboard(register);
+                        }
+                    }
+                    EditorCommand::Paste => {
+                        let register = self.clipboard.paste();
+                        self.view_mut().paste(&register);
+                    }
+                    EditorCommand::Search => self.start_search(),
+                    EditorCommand::Replace => self.start_prompt(PromptType::ReplaceFind, "Replace (regex): "),
+                    EditorCommand::Open => self.start_prompt(PromptType::Open, "Open: "),
+                    EditorCommand::NextBuffer => {
+                        self.buffers.next(&mut self.windows[self.focus].view);
+                        self.show_current_buffer();
+                    }
+                    EditorCommand::PreviousBuffer => {
+                        self.buffers.previous(&mut self.windows[self.focus].view);
+                        self.show_current_buffer();
+                    }
+                    EditorCommand::ListBuffers => self.list_buffers(),
+                    EditorCommand::CloseBuffer => self.handle_close_buffer(),
+                    EditorCommand::SplitWindow(direction) => self.split_window(direction),
+                    EditorCommand::CloseWindow => self.close_window(),
+                    EditorCommand::FocusWindow(dir) => self.move_focus(dir),
+                    EditorCommand::ResizeWindow(dir) => self.resize_window(dir),
+                    _ => self.view_mut().handle_command(cmd),
                 }
             }
         } else {
@@ -118,30 +548,630 @@ impl Editor {
                 // panic!("Received and discarded unsupported or non-press event.");
             }
         }
+        self.sync_windows();
+    }
+
+    // 终端只报告按下, 双击要自己判断: 短时间内在同一位置按了两次
+    fn detect_double_click(&mut self, cmd: EditorCommand) -> EditorCommand {
+        let EditorCommand::Click(position) = cmd else {
+            return cmd;
+        };
+        let now = Instant::now();
+        let is_double = self.last_click.is_some_and(|(time, last_position)| {
+            last_position == position && now.duration_since(time) <= DOUBLE_CLICK_INTERVAL
+        });
+        if is_double {
+            self.last_click = None;
+            EditorCommand::DoubleClick(position)
+        } else {
+            self.last_click = Some((now, position));
+            cmd
+        }
+    }
+
+    fn copy_to_clipboard(&mut self, register: Register) {
+        if let Err(err) = self.clipboard.copy(register, &mut self.terminal) {
+            self.message_bar
+                .update_message(&format!("System clipboard unavailable, copied internally: {err}"));
+        }
     }
 
-    fn refresh_screen(&mut self) {
+    // 没有修改时直接退出, 否则要连按几次Ctrl-Q, 或者在提示中选择怎么处理
+    fn handle_quit(&mut self) {
+        let Some(index) = self.buffers.first_modified() else {
+            self.should_quit = true;
+            return;
+        };
+        self.quit_presses = self.quit_presses.saturating_add(1);
+        let remaining = self.config.quit_times.saturating_sub(self.quit_presses);
+        if remaining == 0 {
+            self.should_quit = true;
+            return;
+        }
+        self.cancel_prompt();
+        // 切换到有修改的buffer, 让用户看到要保存的是哪一个
+        if self.buffers.index_of(self.view()) != Some(index) {
+            self.buffers.switch_to(&mut self.windows[self.focus].view, index);
+            self.refresh_status();
+        }
+        self.start_prompt(
+            PromptType::QuitConfirm,
+            &format!("Unsaved changes! (s)ave & quit, (d)iscard, (c)ancel [Ctrl-Q x{remaining}]"),
+        );
+    }
+
+    // 放弃当前的输入框, 并恢复输入框开始之前的状态
+    fn cancel_prompt(&mut self) {
+        match self.prompt_type {
+            PromptType::Search => self.view_mut().dismiss_search(),
+            PromptType::ReplaceConfirm => {
+                self.view_mut().exit_replace();
+            }
+            PromptType::ReplaceFind | PromptType::ReplaceWith => self.replace_regex = None,
+            PromptType::SaveAs => self.after_save = AfterSave::Nothing,
+            PromptType::BufferList => self.hide_buffer_picker(),
+            PromptType::QuitConfirm
+            | PromptType::CloseConfirm
+            | PromptType::Encoding
+            | PromptType::Open
+            | PromptType::None => {}
+        }
+        if self.prompt_type != PromptType::None {
+            self.end_prompt();
+        }
+    }
+
+    fn start_search(&mut self) {
+        self.start_prompt(PromptType::Search, "Search (Esc to cancel, Up/Down to navigate): ");
+        self.view_mut().enter_search();
+    }
+
+    fn start_prompt(&mut self, prompt_type: PromptType, prompt: &str) {
+        self.prompt_type = prompt_type;
+        self.message_bar.start_prompt(prompt);
+    }
+
+    fn process_prompt_command(&mut self, cmd: EditorCommand) {
+        match (self.prompt_type, &cmd) {
+            // 有内容的时候, Up/Down用来在匹配之间跳转, 否则用来浏览历史
+            (PromptType::Search, EditorCommand::Move(Direction::Up)) if !self.message_bar.value().is_empty() => {
+                self.view_mut().search_prev();
+            }
+            (PromptType::Search, EditorCommand::Move(Direction::Down)) if !self.message_bar.value().is_empty() => {
+                self.view_mut().search_next();
+            }
+            (PromptType::Search, _) => match self.message_bar.handle_prompt_command(cmd) {
+                PromptResult::Changed(query) => self.view_mut().search(&query),
+                PromptResult::Submitted(_) => {
+                    self.view_mut().exit_search();
+                    self.end_prompt();
+                }
+                PromptResult::Cancelled => {
+                    self.view_mut().dismiss_search();
+                    self.end_prompt();
+                }
+                PromptResult::Unchanged => {}
+            },
+            (PromptType::ReplaceFind, _) => match self.message_bar.handle_prompt_command(cmd) {
+                PromptResult::Submitted(value) => match Regex::new(&value) {
+                    Ok(regex) => {
+                        self.replace_regex = Some(regex);
+                        self.start_prompt(PromptType::ReplaceWith, "Replace with: ");
+                    }
+                    Err(_) => {
+                        self.end_prompt();
+                        self.message_bar.update_message("Invalid regular expression");
+                    }
+                },
+                PromptResult::Cancelled => self.end_prompt(),
+                PromptResult::Changed(_) | PromptResult::Unchanged => {}
+            },
+            (PromptType::ReplaceWith, _) => match self.message_bar.handle_prompt_command(cmd) {
+                PromptResult::Submitted(template) => {
+                    if let Some(regex) = self.replace_regex.take() {
+                        if self.view_mut().enter_replace(regex, &template) {
+                            self.start_prompt(
+                                PromptType::ReplaceConfirm,
+                                "Replace this occurrence? (y)es/(n)o/(a)ll/(q)uit",
+                            );
+                        } else {
+                            self.view_mut().exit_replace();
+                            self.end_prompt();
+                            self.message_bar.update_message("No matches found");
+                        }
+                    }
+                }
+                PromptResult::Cancelled => {
+                    self.replace_regex = None;
+                    self.end_prompt();
+                }
+                PromptResult::Changed(_) | PromptResult::Unchanged => {}
+            },
+            (PromptType::SaveAs, _) => match self.message_bar.handle_prompt_command(cmd) {
+                PromptResult::Submitted(filename) if !filename.is_empty() => {
+                    self.end_prompt();
+                    self.save_as(&filename);
+                }
+                PromptResult::Submitted(_) | PromptResult::Cancelled => {
+                    self.end_prompt();
+                    self.after_save = AfterSave::Nothing;
+                    self.message_bar.update_message("Save aborted");
+                }
+                PromptResult::Changed(_) | PromptResult::Unchanged => {}
+            },
+            (PromptType::Encoding, _) => match self.message_bar.handle_prompt_command(cmd) {
+                PromptResult::Submitted(name) => {
+                    self.end_prompt();
+                    self.reopen_with_encoding(&name);
+                }
+                PromptResult::Cancelled => self.end_prompt(),
+                PromptResult::Changed(_) | PromptResult::Unchanged => {}
+            },
+            (PromptType::Open, _) => match self.message_bar.handle_prompt_command(cmd) {
+                PromptResult::Submitted(filename) => {
+                    self.end_prompt();
+                    if !filename.is_empty() {
+                        self.open(&filename);
+                    }
+                }
+                PromptResult::Cancelled => self.end_prompt(),
+                PromptResult::Changed(_) | PromptResult::Unchanged => {}
+            },
+            (PromptType::BufferList, EditorCommand::Move(Direction::Up)) => self.buffer_picker.select_prev(),
+            (PromptType::BufferList, EditorCommand::Move(Direction::Down)) => self.buffer_picker.select_next(),
+            (PromptType::BufferList, EditorCommand::Enter) => {
+                let index = self.buffer_picker.selected();
+                self.cancel_prompt();
+                self.switch_buffer(index);
+            }
+            // 数字键直接选择对应的buffer
+            (PromptType::BufferList, EditorCommand::Insert(ch)) => {
+                let index = ch.to_digit(10).and_then(|digit| (digit as usize).checked_sub(1));
+                if let Some(index) = index.filter(|&index| index < self.buffer_picker.len()) {
+                    self.cancel_prompt();
+                    self.switch_buffer(index);
+                }
+            }
+            (PromptType::BufferList, EditorCommand::Dismiss) => self.cancel_prompt(),
+            (PromptType::QuitConfirm, EditorCommand::Insert('s')) => {
+                self.end_prompt();
+                self.after_save = AfterSave::Quit;
+                self.save();
+            }
+            (PromptType::QuitConfirm, EditorCommand::Insert('d')) => self.should_quit = true,
+            (PromptType::QuitConfirm, EditorCommand::Insert('c') | EditorCommand::Dismiss) => {
+                self.end_prompt();
+                self.message_bar.update_message("Quit cancelled");
+            }
+            (PromptType::CloseConfirm, EditorCommand::Insert('s')) => {
+                self.end_prompt();
+                self.after_save = AfterSave::CloseBuffer;
+                self.save();
+            }
+            (PromptType::CloseConfirm, EditorCommand::Insert('d')) => {
+                self.end_prompt();
+                self.close_buffer();
+            }
+            (PromptType::CloseConfirm, EditorCommand::Insert('c') | EditorCommand::Dismiss) => {
+                self.end_prompt();
+                self.message_bar.update_message("Close cancelled");
+            }
+            (PromptType::ReplaceConfirm, EditorCommand::Insert('y')) => {
+                if !self.view_mut().replace_current() {
+                    self.finish_replace();
+                }
+            }
+            (PromptType::ReplaceConfirm, EditorCommand::Insert('n')) => {
+                if !self.view_mut().skip_current() {
+                    self.finish_replace();
+                }
+            }
+            (PromptType::ReplaceConfirm, EditorCommand::Insert('a')) => {
+                self.view_mut().replace_all();
+                self.finish_replace();
+            }
+            (PromptType::ReplaceConfirm, EditorCommand::Insert('q') | EditorCommand::Dismiss) => {
+                self.finish_replace();
+            }
+            (
+                PromptType::ReplaceConfirm
+                | PromptType::QuitConfirm
+                | PromptType::CloseConfirm
+                | PromptType::BufferList
+                | PromptType::None,
+                _,
+            ) => {}
+        }
+    }
+
+    fn save(&mut self) {
+        if !self.view().has_file_name() {
+            self.start_prompt(PromptType::SaveAs, "Save as: ");
+            return;
+        }
+        match self.view_mut().save() {
+            Ok(()) => {
+                self.message_bar.update_message("File saved");
+                self.finish_save();
+            }
+            Err(err) => {
+                self.after_save = AfterSave::Nothing;
+                self.message_bar.update_message(&format!("Save failed: {err}"));
+            }
+        }
+    }
+
+    fn save_as(&mut self, filename: &str) {
+        match self.view_mut().save_as(filename) {
+            Ok(()) => {
+                // 文件名变了, 标题也要跟着变
+                self.refresh_status();
+                self.message_bar.update_message(&format!("Saved as {filename}"));
+                self.finish_save();
+            }
+            Err(err) => {
+                self.after_save = AfterSave::Nothing;
+                self.message_bar.update_message(&format!("Save failed: {err}"));
+            }
+        }
+    }
+
+    // 保存成功之后继续之前选择的退出或者关闭
+    // 退出时其他buffer可能还有修改, 再检查一遍
+    fn finish_save(&mut self) {
+        match mem::take(&mut self.after_save) {
+            AfterSave::Quit => self.handle_quit(),
+            AfterSave::CloseBuffer => self.close_buffer(),
+            AfterSave::Nothing => {}
+        }
+    }
+
+    fn reopen_with_encoding(&mut self, name: &str) {
+        let Some(encoding) = Encoding::from_name(name) else {
+            let names: Vec<String> = Encoding::ALL.iter().map(Encoding::to_string).collect();
+            self.message_bar
+                .update_message(&format!("Unknown encoding, try one of: {}", names.join(", ")));
+            return;
+        };
+        match self.view_mut().reload_with_encoding(encoding) {
+            Ok(()) => {
+                self.refresh_status();
+                self.message_bar.update_message(&format!("Reopened as {encoding}"));
+            }
+            Err(err) => self.message_bar.update_message(&format!("Could not reopen: {err}")),
+        }
+    }
+
+    fn finish_replace(&mut self) {
+        let count = self.view_mut().exit_replace();
+        self.end_prompt();
+        self.message_bar.update_message(&format!("Replaced {count} occurrence(s)"));
+    }
+
+    fn end_prompt(&mut self) {
+        self.prompt_type = PromptType::None;
+        self.message_bar.end_prompt();
+    }
+
+    pub fn refresh_screen(&mut self) {
         if self.terminal_size.height == 0 || self.terminal_size.width == 0 {
             return;
         }
-        let _ = Terminal::hide_caret();
-        if self.terminal_size.height > 1 {
-            self.status_bar.render(self.terminal_size.height.saturating_sub(2));
+        // buffer列表盖在窗口上面, 窗口重画之后它也要跟着重画
+        if self.windows.iter().any(Window::needs_redraw) {
+            self.buffer_picker.mark_redraw(true);
         }
-        if self.terminal_size.height > 2 {
-            self.view.render(0);
+        for window in &mut self.windows {
+            let origin = window.area().origin;
+            window.render(origin, &mut self.screen);
         }
-        let _ = Terminal::move_caret_to(self.view.caret_position());
-        let _ = Terminal::show_caret();
-        let _ = Terminal::execute();
+        let style = self.theme.separator;
+        for separator in &self.separators {
+            for row in separator.origin.row..separator.origin.row + separator.size.height {
+                let origin = Position { row, ..separator.origin };
+                self.screen.print_styled(origin, 1, style, &[StyledText::new("│", style)]);
+            }
+        }
+        self.buffer_picker.render(Position::default(), &mut self.screen);
+        let bottom_row = self.terminal_size.height.saturating_sub(1);
+        self.message_bar.render(Position { row: bottom_row, col: 0 }, &mut self.screen);
+        let terminal = &mut self.terminal;
+        let changes = self.screen.diff(&self.previous);
+        if !changes.is_empty() {
+            let _ = terminal.hide_caret();
+            if terminal.draw(&changes).is_ok() {
+                self.previous.clone_from(&self.screen);
+            } else {
+                // 不知道终端上画到了哪里, 下次全部重画
+                // 空的Screen里没有任何格子, 和它比较时每个格子都算变化了, 空白的格子也会重画
+                self.previous = Screen::default();
+            }
+        }
+        let caret_position = if self.message_bar.is_prompting() {
+            Some(Position {
+                col: self.message_bar.caret_position_col(),
+                row: bottom_row,
+            })
+        } else {
+            self.windows[self.focus].caret_position()
+        };
+        // 光标被滚出屏幕时不显示
+        if let Some(caret_position) = caret_position {
+            let _ = terminal.move_caret_to(caret_position);
+            let _ = terminal.show_caret();
+        }
+        let _ = terminal.execute();
     }
 }
 
-impl Drop for Editor {
+impl<B: Backend> Drop for Editor<B> {
     fn drop(&mut self) {
-        let _ = Terminal::terminate();
+        let _ = self.terminal.terminate();
         if self.should_quit {
-            let _ = Terminal::print("Goodbye.\r\n");
+            let _ = self.terminal.print("Goodbye.\r\n");
+            let _ = self.terminal.execute();
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+
+    use super::Editor;
+    use crate::clipboard::{Clipboard, ClipboardBackend};
+    use crate::indent::Indentation;
+    use crate::line::TabStops;
+    use crate::memorybackend::MemoryBackend;
+    use crate::terminal::{Position, Size};
+    use crate::theme::Theme;
+    use crate::uicomponent::UIComponent;
+
+    fn editor_with_size(height: usize, width: usize) -> Editor<MemoryBackend> {
+        let mut editor = Editor::with_backend(MemoryBackend::new(Size { height, width })).unwrap();
+        editor.refresh_screen();
+        editor
+    }
+
+    // 40x6的终端: 4行正文, 1行状态栏, 1行消息栏
+    fn editor() -> Editor<MemoryBackend> {
+        editor_with_size(6, 40)
+    }
+
+    // 60x8的终端左右分成两个窗口, 每个窗口6行正文, 左边的窗口30列宽
+    // 分割之前先粘贴text, 分割之后右边的窗口获得焦点
+    fn split_editor(text: &str) -> Editor<MemoryBackend> {
+        let mut editor = editor_with_size(8, 60);
+        editor.evaluate_event(Event::Paste(String::from(text)));
+        press(&mut editor, KeyCode::Char('v'), KeyModifiers::ALT);
+        editor.refresh_screen();
+        editor
+    }
+
+    fn left_window_rows(editor: &Editor<MemoryBackend>) -> Vec<String> {
+        editor.terminal().screen()[..6]
+            .iter()
+            .map(|row| row.chars().take(30).collect::<String>().trim_end().to_string())
+            .collect()
+    }
+
+    // 制表符显示成箭头, 好和空格区分开; expand_tabs时按Tab插入空格
+    fn indent_editor(expand_tabs: bool) -> Editor<MemoryBackend> {
+        let mut editor = editor();
+        editor.windows[0].view.set_tabs(TabStops { width: 4, visible: true });
+        editor.windows[0].view.set_indentation(Indentation { width: 4, expand_tabs });
+        editor
+    }
+
+    fn press(editor: &mut Editor<MemoryBackend>, code: KeyCode, modifiers: KeyModifiers) {
+        editor.evaluate_event(Event::Key(KeyEvent::new(code, modifiers)));
+    }
+
+    fn type_text(editor: &mut Editor<MemoryBackend>, text: &str) {
+        for c in text.chars() {
+            press(editor, KeyCode::Char(c), KeyModifiers::NONE);
+        }
+    }
+
+    fn caret(editor: &Editor<MemoryBackend>) -> Option<(usize, usize)> {
+        editor.terminal().caret().map(|Position { row, col }| (row, col))
+    }
+
+    #[test]
+    fn empty_buffer_shows_welcome() {
+        let editor = editor();
+        let screen = editor.terminal().screen();
+        assert_eq!(screen[0], "~");
+        assert!(screen[1].contains("editor -- version"));
+        assert!(screen[4].starts_with("[No Name] - 0 lines"));
+        assert!(screen[5].starts_with("HELP:"));
+        // 行号占了两列
+        assert_eq!(caret(&editor), Some((0, 2)));
+    }
+
+    #[test]
+    fn typed_keys_are_drawn() {
+        let mut editor = editor();
+        type_text(&mut editor, "hi");
+        editor.refresh_screen();
+        let screen = editor.terminal().screen();
+        assert_eq!(screen[..4], ["1 hi", "~", "~", "~"]);
+        assert!(screen[4].starts_with("[No Name] - 1 lines (modified)"));
+        assert_eq!(caret(&editor), Some((0, 4)));
+        assert_eq!(editor.terminal().title(), "[No Name] - rs-editor");
+        let status = editor.terminal().cell(Position { row: 4, col: 0 }).unwrap();
+        assert_eq!(status.style, Theme::default().status_bar);
+    }
+
+    #[test]
+    fn paste_inserts_every_line() {
+        let mut editor = editor();
+        type_text(&mut editor, "hi");
+        editor.evaluate_event(Event::Paste(String::from("one\ntwo")));
+        editor.refresh_screen();
+        assert_eq!(editor.terminal().screen()[..3], ["1 hione", "2 two", "~"]);
+        assert_eq!(caret(&editor), Some((1, 5)));
+    }
+
+    #[test]
+    fn resize_moves_status_and_message_bar() {
+        let mut editor = editor();
+        editor.evaluate_event(Event::Paste(String::from("one\ntwo\nthree")));
+        editor.evaluate_event(Event::Resize(20, 4));
+        editor.refresh_screen();
+        // 只剩两行正文, 光标所在的第三行滚进来
+        let screen = editor.terminal().screen();
+        assert_eq!(screen[..2], ["2 two", "3 three"]);
+        assert!(screen[2].starts_with("[No Name] - 3"));
+        assert_eq!(screen[3], "HELP: Ctrl-F = find");
+        assert_eq!(caret(&editor), Some((1, 7)));
+    }
+
+    #[test]
+    fn unchanged_screen_draws_nothing() {
+        let mut editor = editor();
+        let drawn = editor.terminal().cells_drawn();
+        editor.refresh_screen();
+        assert_eq!(editor.terminal().cells_drawn(), drawn);
+        // 在行首前面按左键什么也不会变
+        press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        editor.refresh_screen();
+        assert_eq!(editor.terminal().cells_drawn(), drawn);
+    }
+
+    #[test]
+    fn osc52_copy_goes_through_backend() {
+        let mut editor = editor();
+        editor.clipboard = Clipboard::new(ClipboardBackend::Osc52);
+        type_text(&mut editor, "hi");
+        press(&mut editor, KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert!(editor.terminal().clipboard().is_some_and(|text| text.starts_with("hi")));
+    }
+
+    #[test]
+    fn sibling_window_follows_edits_above_its_caret() {
+        let mut editor = split_editor("a\nb\nc");
+        for _ in 0..2 {
+            press(&mut editor, KeyCode::Up, KeyModifiers::NONE);
+        }
+        press(&mut editor, KeyCode::Home, KeyModifiers::NONE);
+        editor.evaluate_event(Event::Paste(String::from("x\n")));
+        editor.refresh_screen();
+        assert_eq!(left_window_rows(&editor)[..4], ["1 x", "2 a", "3 b", "4 c"]);
+        // 左边窗口的光标还在c后面
+        press(&mut editor, KeyCode::Left, KeyModifiers::ALT);
+        editor.refresh_screen();
+        assert_eq!(caret(&editor), Some((3, 3)));
+    }
+
+    #[test]
+    fn sibling_window_keeps_its_scroll_position() {
+        let text: Vec<String> = (0..20).map(|index| index.to_string()).collect();
+        let mut editor = split_editor(&text.join("\n"));
+        assert_eq!(left_window_rows(&editor)[0], "15 14");
+        for _ in 0..19 {
+            press(&mut editor, KeyCode::Up, KeyModifiers::NONE);
+        }
+        editor.evaluate_event(Event::Paste(String::from("new\n")));
+        editor.refresh_screen();
+        // 上面多了一行, 左边窗口显示的还是原来的那几行
+        assert_eq!(left_window_rows(&editor)[0], "16 14");
+        assert_eq!(left_window_rows(&editor)[5], "21 19");
+    }
+
+    #[test]
+    fn soft_wrapped_sibling_keeps_its_scroll_position() {
+        let mut editor = editor_with_size(8, 60);
+        press(&mut editor, KeyCode::Char('w'), KeyModifiers::ALT);
+        let text: Vec<String> = (0..20).map(|index| index.to_string()).collect();
+        editor.evaluate_event(Event::Paste(text.join("\n")));
+        press(&mut editor, KeyCode::Char('v'), KeyModifiers::ALT);
+        editor.refresh_screen();
+        assert_eq!(left_window_rows(&editor)[0], "15 14");
+        for _ in 0..19 {
+            press(&mut editor, KeyCode::Up, KeyModifiers::NONE);
+        }
+        // 插入的行在左边窗口里要折成两个屏幕行
+        editor.evaluate_event(Event::Paste(format!("{}\n", "w".repeat(40))));
+        editor.refresh_screen();
+        assert_eq!(left_window_rows(&editor)[0], "16 14");
+    }
+
+    #[test]
+    fn sibling_window_is_not_redrawn_without_changes() {
+        let mut editor = split_editor("a\nb\nc");
+        press(&mut editor, KeyCode::Up, KeyModifiers::NONE);
+        assert!(!editor.windows[0].needs_redraw());
+        press(&mut editor, KeyCode::Char('x'), KeyModifiers::NONE);
+        assert!(editor.windows[0].needs_redraw());
+    }
+
+    #[test]
+    fn scroll_moves_the_window_under_the_mouse() {
+        let text: Vec<String> = (0..20).map(|index| index.to_string()).collect();
+        let mut editor = split_editor(&text.join("\n"));
+        let before = caret(&editor);
+        assert_eq!(left_window_rows(&editor)[0], "15 14");
+        editor.evaluate_event(Event::Mouse(MouseEvent {
+            kind: MouseEventKind::ScrollUp,
+            column: 5,
+            row: 2,
+            modifiers: KeyModifiers::NONE,
+        }));
+        editor.refresh_screen();
+        // 左边窗口滚动了, 焦点和光标都没有动
+        assert_eq!(left_window_rows(&editor)[0], "12 11");
+        assert_eq!(editor.focus, 1);
+        assert_eq!(caret(&editor), before);
+    }
+
+    #[test]
+    fn backspace_removes_one_indent_level() {
+        let mut editor = indent_editor(true);
+        press(&mut editor, KeyCode::Tab, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Tab, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Backspace, KeyModifiers::NONE);
+        type_text(&mut editor, "x");
+        editor.refresh_screen();
+        assert_eq!(editor.terminal().screen()[0], "1     x");
+        // 不在制表位上时退回到上一个制表位
+        editor.evaluate_event(Event::Paste(String::from("\n      ")));
+        press(&mut editor, KeyCode::Backspace, KeyModifiers::NONE);
+        type_text(&mut editor, "y");
+        editor.refresh_screen();
+        assert_eq!(editor.terminal().screen()[1], "2     y");
+    }
+
+    #[test]
+    fn enter_copies_leading_whitespace() {
+        let mut editor = indent_editor(false);
+        editor.evaluate_event(Event::Paste(String::from("\t  foo")));
+        press(&mut editor, KeyCode::Enter, KeyModifiers::NONE);
+        type_text(&mut editor, "x");
+        editor.refresh_screen();
+        assert_eq!(editor.terminal().screen()[..2], ["1 →     foo", "2 →     x"]);
+    }
+
+    #[test]
+    fn dedent_selection_with_mixed_indentation() {
+        let mut editor = indent_editor(false);
+        editor.evaluate_event(Event::Paste(String::from("\tfoo\n      bar\n  baz")));
+        press(&mut editor, KeyCode::Up, KeyModifiers::SHIFT);
+        press(&mut editor, KeyCode::Up, KeyModifiers::SHIFT);
+        press(&mut editor, KeyCode::BackTab, KeyModifiers::SHIFT);
+        editor.refresh_screen();
+        // 制表符删掉一个, 空格退回到上一个制表位
+        assert_eq!(editor.terminal().screen()[..3], ["1 foo", "2     bar", "3 baz"]);
+    }
+
+    #[test]
+    fn selection_ending_at_line_start_does_not_indent_that_line() {
+        let mut editor = indent_editor(false);
+        editor.evaluate_event(Event::Paste(String::from("a\nb\nc")));
+        press(&mut editor, KeyCode::Home, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Up, KeyModifiers::SHIFT);
+        press(&mut editor, KeyCode::Up, KeyModifiers::SHIFT);
+        press(&mut editor, KeyCode::Tab, KeyModifiers::NONE);
+        editor.refresh_screen();
+        assert_eq!(editor.terminal().screen()[..3], ["1 →   a", "2 →   b", "3 c"]);
+    }
+}
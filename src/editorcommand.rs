@@ -1,5 +1,6 @@
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use crate::terminal::Size;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use crate::layout::SplitDirection;
+use crate::terminal::{Position, Size};
 
 #[derive(Clone, Copy)]
 pub enum Direction {
@@ -13,16 +14,89 @@ pub enum Direction {
     Down,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum EditorCommand {
     Move(Direction),
+    // 按住Shift移动光标, 扩展选区
+    Select(Direction),
     Resize(Size),
+    // 鼠标左键按下/双击/拖动的位置, 相对于屏幕左上角
+    Click(Position),
+    DoubleClick(Position),
+    Drag(Position),
+    // 滚轮和滚动时鼠标的位置, 只滚动画面不移动光标
+    Scroll(Direction, Position),
     Insert(char),
+    // 终端的bracketed paste模式下粘贴进来的整段文本
+    PasteText(String),
+    // Tab和Shift-Tab: 有选区时缩进/取消缩进选中的所有行
+    Indent,
+    Dedent,
     Backspace,
     Delete,
     Enter,
     Quit,
     Save,
+    SaveAs,
+    ToggleLineEnding,
+    ReopenWithEncoding,
+    ToggleSoftWrap,
+    CycleLineNumbers,
+    Undo,
+    Redo,
+    Search,
+    Cut,
+    Copy,
+    Paste,
+    Replace,
+    // 打开文件, 切换/列出/关闭打开的buffer
+    Open,
+    NextBuffer,
+    PreviousBuffer,
+    ListBuffers,
+    CloseBuffer,
+    // 分割/关闭窗口, 把焦点移到某个方向的窗口, 往某个方向放大/缩小当前窗口
+    SplitWindow(SplitDirection),
+    CloseWindow,
+    FocusWindow(Direction),
+    ResizeWindow(Direction),
+    Dismiss,
+}
+
+impl EditorCommand {
+    // 会修改buffer内容的命令, 只读的buffer要拒绝这些命令
+    pub const fn is_edit(&self) -> bool {
+        matches!(
+            self,
+            Self::Insert(_)
+                | Self::PasteText(_)
+                | Self::Indent
+                | Self::Dedent
+                | Self::Backspace
+                | Self::Delete
+                | Self::Enter
+                | Self::Undo
+                | Self::Redo
+                | Self::Replace
+                | Self::ToggleLineEnding
+                | Self::Cut
+                | Self::Paste
+        )
+    }
+
+    fn direction_from(code: KeyCode) -> Result<Direction, String> {
+        match code {
+            KeyCode::Up => Ok(Direction::Up),
+            KeyCode::Down => Ok(Direction::Down),
+            KeyCode::Left => Ok(Direction::Left),
+            KeyCode::Right => Ok(Direction::Right),
+            KeyCode::PageDown => Ok(Direction::PageDown),
+            KeyCode::PageUp => Ok(Direction::PageUp),
+            KeyCode::End => Ok(Direction::End),
+            KeyCode::Home => Ok(Direction::Home),
+            _ => Err(format!("Key Code is not a direction: {code:?}")),
+        }
+    }
 }
 
 impl TryFrom<Event> for EditorCommand {
@@ -43,10 +117,109 @@ impl TryFrom<Event> for EditorCommand {
                     if modifiers == KeyModifiers::CONTROL => {
                         Ok(EditorCommand::Save)
                     }
+                    KeyCode::Char('s')
+                    if modifiers == KeyModifiers::ALT => {
+                        Ok(EditorCommand::SaveAs)
+                    }
+                    KeyCode::Char('l')
+                    if modifiers == KeyModifiers::ALT => {
+                        Ok(EditorCommand::ToggleLineEnding)
+                    }
+                    KeyCode::Char('e')
+                    if modifiers == KeyModifiers::ALT => {
+                        Ok(EditorCommand::ReopenWithEncoding)
+                    }
+                    KeyCode::Char('w')
+                    if modifiers == KeyModifiers::ALT => {
+                        Ok(EditorCommand::ToggleSoftWrap)
+                    }
+                    KeyCode::Char('n')
+                    if modifiers == KeyModifiers::ALT => {
+                        Ok(EditorCommand::CycleLineNumbers)
+                    }
+                    KeyCode::Char('x')
+                    if modifiers == KeyModifiers::CONTROL => {
+                        Ok(EditorCommand::Cut)
+                    }
+                    KeyCode::Char('c')
+                    if modifiers == KeyModifiers::CONTROL => {
+                        Ok(EditorCommand::Copy)
+                    }
+                    KeyCode::Char('v')
+                    if modifiers == KeyModifiers::CONTROL => {
+                        Ok(EditorCommand::Paste)
+                    }
+                    KeyCode::Char('z')
+                    if modifiers == KeyModifiers::CONTROL => {
+                        Ok(EditorCommand::Undo)
+                    }
+                    KeyCode::Char('y')
+                    if modifiers == KeyModifiers::CONTROL => {
+                        Ok(EditorCommand::Redo)
+                    }
+                    KeyCode::Char('f')
+                    if modifiers == KeyModifiers::CONTROL => {
+                        Ok(EditorCommand::Search)
+                    }
+                    KeyCode::Char('r')
+                    if modifiers == KeyModifiers::CONTROL => {
+                        Ok(EditorCommand::Replace)
+                    }
+                    KeyCode::Char('o')
+                    if modifiers == KeyModifiers::CONTROL => {
+                        Ok(EditorCommand::Open)
+                    }
+                    KeyCode::Char('.')
+                    if modifiers == KeyModifiers::ALT => {
+                        Ok(EditorCommand::NextBuffer)
+                    }
+                    KeyCode::Char(',')
+                    if modifiers == KeyModifiers::ALT => {
+                        Ok(EditorCommand::PreviousBuffer)
+                    }
+                    KeyCode::Char('b')
+                    if modifiers == KeyModifiers::CONTROL => {
+                        Ok(EditorCommand::ListBuffers)
+                    }
+                    KeyCode::Char('w')
+                    if modifiers == KeyModifiers::CONTROL => {
+                        Ok(EditorCommand::CloseBuffer)
+                    }
+                    KeyCode::Char('h')
+                    if modifiers == KeyModifiers::ALT => {
+                        Ok(EditorCommand::SplitWindow(SplitDirection::Horizontal))
+                    }
+                    KeyCode::Char('v')
+                    if modifiers == KeyModifiers::ALT => {
+                        Ok(EditorCommand::SplitWindow(SplitDirection::Vertical))
+                    }
+                    KeyCode::Char('q')
+                    if modifiers == KeyModifiers::ALT => {
+                        Ok(EditorCommand::CloseWindow)
+                    }
                     KeyCode::Char(ch)
                     if modifiers == KeyModifiers::NONE || modifiers == KeyModifiers::SHIFT => {
                         Ok(EditorCommand::Insert(ch))
                     }
+                    KeyCode::Up
+                    | KeyCode::Down
+                    | KeyCode::Left
+                    | KeyCode::Right
+                    | KeyCode::PageDown
+                    | KeyCode::PageUp
+                    | KeyCode::End
+                    | KeyCode::Home
+                    if modifiers == KeyModifiers::SHIFT => {
+                        Self::direction_from(code).map(EditorCommand::Select)
+                    }
+                    KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right
+                    if modifiers == KeyModifiers::ALT => {
+                        Self::direction_from(code).map(EditorCommand::FocusWindow)
+                    }
+                    KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right
+                    if modifiers == KeyModifiers::ALT | KeyModifiers::SHIFT => {
+                        Self::direction_from(code).map(EditorCommand::ResizeWindow)
+                    }
                     KeyCode::Up => Ok(EditorCommand::Move(Direction::Up)),
                     KeyCode::Down => Ok(EditorCommand::Move(Direction::Down)),
                     KeyCode::Left => Ok(EditorCommand::Move(Direction::Left)),
@@ -58,10 +231,26 @@ impl TryFrom<Event> for EditorCommand {
                     KeyCode::Delete => Ok(EditorCommand::Delete),
                     KeyCode::Backspace => Ok(EditorCommand::Backspace),
                     KeyCode::Enter => Ok(EditorCommand::Enter),
-                    KeyCode::Tab => Ok(EditorCommand::Insert('\t')),
+                    KeyCode::Tab => Ok(EditorCommand::Indent),
+                    KeyCode::BackTab => Ok(EditorCommand::Dedent),
+                    KeyCode::Esc => Ok(EditorCommand::Dismiss),
                     _ => Err(format!("Key Code not supported: {code:?}")),
                 }
             }
+            Event::Mouse(MouseEvent { kind, column, row, .. }) => {
+                let position = Position {
+                    col: column as usize,
+                    row: row as usize,
+                };
+                match kind {
+                    MouseEventKind::Down(MouseButton::Left) => Ok(EditorCommand::Click(position)),
+                    MouseEventKind::Drag(MouseButton::Left) => Ok(EditorCommand::Drag(position)),
+                    MouseEventKind::ScrollUp => Ok(EditorCommand::Scroll(Direction::Up, position)),
+                    MouseEventKind::ScrollDown => Ok(EditorCommand::Scroll(Direction::Down, position)),
+                    _ => Err(format!("Mouse event not supported: {kind:?}")),
+                }
+            }
+            Event::Paste(text) => Ok(EditorCommand::PasteText(text)),
             Event::Resize(col, row) => {
                 let size = Size {
                     width: col as usize,
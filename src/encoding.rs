@@ -0,0 +1,256 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+
+use encoding_rs::{GB18030, GBK};
+
+// 文件的字符编码
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Gbk,
+    Gb18030,
+}
+
+impl Encoding {
+    pub const ALL: [Self; 6] = [
+        Self::Utf8,
+        Self::Utf16Le,
+        Self::Utf16Be,
+        Self::Latin1,
+        Self::Gbk,
+        Self::Gb18030,
+    ];
+
+    // 猜测文件的编码: 先看BOM, 再看是不是合法的UTF-8, 然后试GB18030, 都不行就当成Latin-1
+    // 能用GBK表示的当成GBK, 保存时不会写出GBK的工具读不了的四字节序列
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&[0xFF, 0xFE]) {
+            return Self::Utf16Le;
+        } else if bytes.starts_with(&[0xFE, 0xFF]) {
+            return Self::Utf16Be;
+        } else if std::str::from_utf8(bytes).is_ok() {
+            return Self::Utf8;
+        }
+        let gb_text = GB18030
+            .decode_without_bom_handling_and_without_replacement(bytes)
+            .filter(|_| Self::looks_like_gb(bytes));
+        match gb_text {
+            Some(text) if GBK.encode(&text).2 => Self::Gb18030,
+            Some(_) => Self::Gbk,
+            None => Self::Latin1,
+        }
+    }
+
+    // Latin-1的文本里, 带重音的字母后面跟着ASCII字母时也能凑成GBK扩展区的双字节字符(比如naïve)
+    // 真正的中文文本里大部分是GB2312的常用字, 两个字节都不小于0xA1
+    fn looks_like_gb(bytes: &[u8]) -> bool {
+        let mut characters = 0;
+        let mut common = 0;
+        let mut index = 0;
+        while let Some(&lead) = bytes.get(index) {
+            if lead < 0x80 {
+                index += 1;
+                continue;
+            }
+            characters += 1;
+            let trail = bytes.get(index + 1).copied().unwrap_or(0);
+            // 四字节序列的第二个字节是数字
+            if trail.is_ascii_digit() {
+                index += 4;
+                continue;
+            }
+            if (0xA1..=0xF7).contains(&lead) && trail >= 0xA1 {
+                common += 1;
+            }
+            index += 2;
+        }
+        common * 2 > characters
+    }
+
+    // 按名字查找编码, 不区分大小写, 也不管中间的'-'和'_'
+    pub fn from_name(name: &str) -> Option<Self> {
+        let normalized: String = name
+            .chars()
+            .filter(|ch| *ch != '-' && *ch != '_')
+            .collect::<String>()
+            .to_lowercase();
+        match normalized.as_str() {
+            "utf8" => Some(Self::Utf8),
+            "utf16le" | "utf16" => Some(Self::Utf16Le),
+            "utf16be" => Some(Self::Utf16Be),
+            "latin1" | "iso88591" => Some(Self::Latin1),
+            "gbk" | "cp936" => Some(Self::Gbk),
+            "gb18030" => Some(Self::Gb18030),
+            _ => None,
+        }
+    }
+
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Self::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Self::Utf16Le => &[0xFF, 0xFE],
+            Self::Utf16Be => &[0xFE, 0xFF],
+            Self::Latin1 | Self::Gbk | Self::Gb18030 => &[],
+        }
+    }
+
+    // 解码整个文件, 同时返回开头有没有BOM
+    pub fn decode(self, bytes: &[u8]) -> Result<(String, bool), Error> {
+        let bom = self.bom();
+        let has_bom = !bom.is_empty() && bytes.starts_with(bom);
+        let bytes = if has_bom { &bytes[bom.len()..] } else { bytes };
+        let text = match self {
+            Self::Utf8 => String::from_utf8(bytes.to_vec()).map_err(|_| self.decode_error())?,
+            Self::Utf16Le | Self::Utf16Be => {
+                if bytes.len() % 2 != 0 {
+                    return Err(self.decode_error());
+                }
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| {
+                        let pair = [pair[0], pair[1]];
+                        if self == Self::Utf16Le {
+                            u16::from_le_bytes(pair)
+                        } else {
+                            u16::from_be_bytes(pair)
+                        }
+                    })
+                    .collect();
+                String::from_utf16(&units).map_err(|_| self.decode_error())?
+            }
+            // Latin-1的每个字节正好对应U+0000到U+00FF
+            Self::Latin1 => bytes.iter().map(|&byte| char::from(byte)).collect(),
+            Self::Gbk | Self::Gb18030 => {
+                let encoding = if self == Self::Gbk { GBK } else { GB18030 };
+                encoding
+                    .decode_without_bom_handling_and_without_replacement(bytes)
+                    .ok_or_else(|| self.decode_error())?
+                    .into_owned()
+            }
+        };
+        Ok((text, has_bom))
+    }
+
+    // 编码整个文件, 有字符没法用这种编码表示时返回错误
+    pub fn encode(self, text: &str, with_bom: bool) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::with_capacity(text.len());
+        if with_bom {
+            bytes.extend_from_slice(self.bom());
+        }
+        match self {
+            Self::Utf8 => bytes.extend_from_slice(text.as_bytes()),
+            Self::Utf16Le => text.encode_utf16().for_each(|unit| bytes.extend(unit.to_le_bytes())),
+            Self::Utf16Be => text.encode_utf16().for_each(|unit| bytes.extend(unit.to_be_bytes())),
+            Self::Latin1 => {
+                for ch in text.chars() {
+                    let byte = u8::try_from(u32::from(ch)).map_err(|_| self.encode_error(ch))?;
+                    bytes.push(byte);
+                }
+            }
+            Self::Gbk | Self::Gb18030 => {
+                let encoding = if self == Self::Gbk { GBK } else { GB18030 };
+                let (encoded, _, had_errors) = encoding.encode(text);
+                if had_errors {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("text contains characters that cannot be encoded in {self}"),
+                    ));
+                }
+                bytes.extend_from_slice(&encoded);
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn decode_error(self) -> Error {
+        Error::new(ErrorKind::InvalidData, format!("file is not valid {self}"))
+    }
+
+    fn encode_error(self, ch: char) -> Error {
+        Error::new(ErrorKind::InvalidData, format!("'{ch}' cannot be encoded in {self}"))
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+            Self::Latin1 => "Latin-1",
+            Self::Gbk => "GBK",
+            Self::Gb18030 => "GB18030",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Encoding;
+
+    #[test]
+    fn detect_utf8_and_bom() {
+        assert_eq!(Encoding::detect("héllo".as_bytes()), Encoding::Utf8);
+        assert_eq!(Encoding::detect(b"\xEF\xBB\xBFabc"), Encoding::Utf8);
+        assert_eq!(Encoding::detect(b"\xFF\xFEa\x00"), Encoding::Utf16Le);
+        assert_eq!(Encoding::detect(b"\xFE\xFF\x00a"), Encoding::Utf16Be);
+    }
+
+    #[test]
+    fn detect_gbk_when_text_fits_in_gbk() {
+        // "中文 abc"
+        assert_eq!(Encoding::detect(b"\xD6\xD0\xCE\xC4 abc"), Encoding::Gbk);
+        // "中文"加上GBK里没有的😀, 只有GB18030的四字节序列能表示
+        assert_eq!(Encoding::detect(b"\xD6\xD0\xCE\xC4\x949\xFC6"), Encoding::Gb18030);
+    }
+
+    #[test]
+    fn detect_latin1_that_looks_like_gb_pairs() {
+        // naïve里的ï和v正好凑成GBK的一个字符
+        assert_eq!(Encoding::detect(b"na\xEFve"), Encoding::Latin1);
+        assert_eq!(Encoding::detect(b"caf\xE9 au lait"), Encoding::Latin1);
+    }
+
+    #[test]
+    fn decode_strips_bom() {
+        assert_eq!(Encoding::Utf8.decode(b"\xEF\xBB\xBFabc").unwrap(), (String::from("abc"), true));
+        assert_eq!(Encoding::Utf8.decode(b"abc").unwrap(), (String::from("abc"), false));
+        assert_eq!(Encoding::Utf16Le.decode(b"\xFF\xFEa\x00b\x00").unwrap(), (String::from("ab"), true));
+        assert_eq!(Encoding::Utf16Be.decode(b"\x00a\x00b").unwrap(), (String::from("ab"), false));
+    }
+
+    #[test]
+    fn decode_rejects_odd_length_utf16() {
+        assert!(Encoding::Utf16Le.decode(b"\xFF\xFEa\x00b").is_err());
+        assert!(Encoding::Utf16Be.decode(b"\x00a\x00").is_err());
+    }
+
+    #[test]
+    fn decode_latin1_and_gb() {
+        assert_eq!(Encoding::Latin1.decode(b"na\xEFve").unwrap().0, "naïve");
+        assert_eq!(Encoding::Gbk.decode(b"\xD6\xD0\xCE\xC4").unwrap().0, "中文");
+        assert!(Encoding::Gbk.decode(b"\xD6").is_err());
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        for encoding in Encoding::ALL {
+            let text = if matches!(encoding, Encoding::Latin1) { "naïve\n" } else { "中文 é\n" };
+            let with_bom = matches!(encoding, Encoding::Utf8 | Encoding::Utf16Le | Encoding::Utf16Be);
+            let bytes = encoding.encode(text, with_bom).unwrap();
+            assert_eq!(encoding.decode(&bytes).unwrap(), (String::from(text), with_bom), "{encoding}");
+        }
+    }
+
+    #[test]
+    fn encode_rejects_characters_outside_the_encoding() {
+        assert!(Encoding::Latin1.encode("中", false).is_err());
+        assert!(Encoding::Gbk.encode("😀", false).is_err());
+        assert_eq!(Encoding::Gb18030.encode("😀", false).unwrap(), b"\x949\xFC6");
+    }
+}
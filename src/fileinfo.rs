@@ -1,18 +1,84 @@
 use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
 
-#[derive(Default, Debug, Clone)]
+use crate::encoding::Encoding;
+use crate::syntax::Language;
+
+// 文件的换行符
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+        }
+    }
+
+    pub const fn toggle(self) -> Self {
+        match self {
+            Self::Lf => Self::Crlf,
+            Self::Crlf => Self::Lf,
+        }
+    }
+}
+
+impl Display for LineEnding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lf => write!(f, "LF"),
+            Self::Crlf => write!(f, "CRLF"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct FileInfo {
     // 文件路径
     pub path: Option<PathBuf>,
+    pub line_ending: LineEnding,
+    pub encoding: Encoding,
+    // 文件开头是否有BOM
+    pub has_bom: bool,
+    // 最后一行是否以换行符结尾
+    pub trailing_newline: bool,
+    // 没有写权限, 或者是二进制文件
+    pub read_only: bool,
+    // 二进制文件按Latin-1逐字节解码, 只能查看
+    pub binary: bool,
+}
+
+impl Default for FileInfo {
+    fn default() -> Self {
+        Self {
+            path: None,
+            line_ending: LineEnding::default(),
+            encoding: Encoding::default(),
+            has_bom: false,
+            trailing_newline: true,
+            read_only: false,
+            binary: false,
+        }
+    }
 }
 
 impl FileInfo {
     pub fn from(filename: &str) -> Self {
         Self {
             path: Some(PathBuf::from(filename)),
+            ..Self::default()
         }
     }
+
+    // 根据文件名选择高亮的语言
+    pub fn language(&self) -> Option<Language> {
+        self.path.as_deref().and_then(Language::from_path)
+    }
 }
 
 impl Display for FileInfo {
@@ -0,0 +1,71 @@
+use std::fmt::{Display, Formatter};
+
+use serde::Deserialize;
+
+use crate::style::StyledText;
+use crate::theme::Theme;
+
+// 行号的显示方式
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineNumbers {
+    Off,
+    #[default]
+    Absolute,
+    // 显示和光标所在行的距离
+    Relative,
+    // 光标所在行显示行号, 其他行显示距离
+    Hybrid,
+}
+
+impl LineNumbers {
+    // 按顺序切换到下一种方式
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Absolute,
+            Self::Absolute => Self::Relative,
+            Self::Relative => Self::Hybrid,
+            Self::Hybrid => Self::Off,
+        }
+    }
+
+    // 行号栏的宽度, 包括和正文之间的一个空格, 随着总行数变化
+    pub fn width(self, line_count: usize) -> usize {
+        if self == Self::Off {
+            return 0;
+        }
+        line_count.max(1).to_string().len().saturating_add(1)
+    }
+
+    // 行号栏的内容, line_index为None时(软换行的后续行, 文件末尾)只有空白
+    pub fn render(self, line_index: Option<usize>, current_line_index: usize, width: usize, theme: &Theme) -> StyledText {
+        let Some(line_index) = line_index.filter(|_| width > 0) else {
+            return StyledText::new(" ".repeat(width), theme.on_text(theme.line_number));
+        };
+        let number = match self {
+            Self::Relative => line_index.abs_diff(current_line_index),
+            Self::Hybrid if line_index != current_line_index => line_index.abs_diff(current_line_index),
+            _ => line_index.saturating_add(1),
+        };
+        // 光标所在行的行号用单独的样式
+        let style = if line_index == current_line_index {
+            theme.current_line_number
+        } else {
+            theme.line_number
+        };
+        let number_width = width.saturating_sub(1);
+        StyledText::new(format!("{number:>number_width$} "), theme.on_text(style))
+    }
+}
+
+impl Display for LineNumbers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Off => "off",
+            Self::Absolute => "absolute",
+            Self::Relative => "relative",
+            Self::Hybrid => "hybrid",
+        };
+        write!(f, "{name}")
+    }
+}
@@ -0,0 +1,146 @@
+use crate::annotation::Annotation;
+use crate::line::Line;
+use crate::syntax::{Language, LineState};
+
+// 一行高亮的结果
+struct HighlightedLine {
+    // 这一行开始时的状态, 和上一行结束时的状态一样就不用重新高亮
+    state_in: LineState,
+    annotations: Vec<Annotation>,
+    state_out: LineState,
+}
+
+// 缓存每一行的高亮结果, 修改某一行之后只重新高亮受影响的行
+#[derive(Default)]
+pub struct Highlighter {
+    language: Option<Language>,
+    // 和buffer的行一一对应, None表示还没有高亮或者被修改过
+    lines: Vec<Option<HighlightedLine>>,
+}
+
+impl Highlighter {
+    pub fn language(&self) -> Option<Language> {
+        self.language
+    }
+
+    pub fn set_language(&mut self, language: Option<Language>) {
+        if self.language != language {
+            self.language = language;
+            self.lines.clear();
+        }
+    }
+
+    // 和buffer的修改同步: 从line_index开始删掉remove行, 插入insert行
+    // 后面的行保留缓存, 如果它们开始时的状态没有变, 就不用重新高亮
+    pub fn splice(&mut self, line_index: usize, remove: usize, insert: usize) {
+        if line_index >= self.lines.len() {
+            return;
+        }
+        let end = (line_index + remove).min(self.lines.len());
+        self.lines.splice(line_index..end, (0..insert).map(|_| None));
+    }
+
+    // 高亮lines的前until行, 缓存里还有效的行直接复用
+    pub fn update(&mut self, lines: &[Line], until: usize) {
+        let Some(language) = self.language else {
+            return;
+        };
+        let until = until.min(lines.len());
+        self.lines.resize_with(lines.len(), || None);
+        let mut state = LineState::default();
+        for (line_index, line) in lines.iter().enumerate().take(until) {
+            let cached = &mut self.lines[line_index];
+            if let Some(highlighted) = cached.as_ref().filter(|highlighted| highlighted.state_in == state) {
+                state = highlighted.state_out;
+                continue;
+            }
+            let (spans, state_out) = language.highlight(&line.to_string(), state);
+            // 高亮的结果是字节下标, 换算成grapheme的下标
+            let offsets = line.byte_offsets();
+            let annotations = spans
+                .into_iter()
+                .map(|span| Annotation {
+                    annotation_type: span.annotation_type,
+                    range: offsets.partition_point(|&offset| offset < span.range.start)
+                        ..offsets.partition_point(|&offset| offset < span.range.end),
+                })
+                .filter(|annotation| !annotation.range.is_empty())
+                .collect();
+            *cached = Some(HighlightedLine {
+                state_in: state,
+                annotations,
+                state_out,
+            });
+            state = state_out;
+        }
+    }
+
+    pub fn annotations(&self, line_index: usize) -> &[Annotation] {
+        self.lines
+            .get(line_index)
+            .and_then(Option::as_ref)
+            .map_or(&[], |highlighted| &highlighted.annotations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Highlighter;
+    use crate::annotation::AnnotationType;
+    use crate::line::Line;
+    use crate::syntax::Language;
+
+    fn lines(text: &[&str]) -> Vec<Line> {
+        text.iter().map(|line| Line::from(*line)).collect()
+    }
+
+    fn types(highlighter: &Highlighter, line_index: usize) -> Vec<AnnotationType> {
+        highlighter.annotations(line_index).iter().map(|annotation| annotation.annotation_type).collect()
+    }
+
+    #[test]
+    fn update_only_rehighlights_lines_whose_state_changed() {
+        let mut highlighter = Highlighter::default();
+        highlighter.set_language(Some(Language::Rust));
+        let mut text = lines(&["let a;", "let b;", "let c;"]);
+        highlighter.update(&text, text.len());
+        assert_eq!(types(&highlighter, 2), [AnnotationType::Keyword]);
+        // 把缓存的结果换掉, 重新高亮之后才会变回来
+        for cached in highlighter.lines.iter_mut().skip(1).flatten() {
+            cached.annotations.clear();
+        }
+
+        // 改了第一行, 结束时的状态没变, 后面的行直接用缓存
+        text[0] = Line::from("let x;");
+        highlighter.splice(0, 1, 1);
+        highlighter.update(&text, text.len());
+        assert_eq!(types(&highlighter, 0), [AnnotationType::Keyword]);
+        assert!(types(&highlighter, 1).is_empty());
+        assert!(types(&highlighter, 2).is_empty());
+
+        // 第一行开始了块注释, 后面的行开始时的状态变了, 都要重新高亮
+        text[0] = Line::from("/* let x;");
+        highlighter.splice(0, 1, 1);
+        highlighter.update(&text, text.len());
+        assert_eq!(types(&highlighter, 1), [AnnotationType::Comment]);
+        assert_eq!(types(&highlighter, 2), [AnnotationType::Comment]);
+    }
+
+    #[test]
+    fn splice_keeps_cache_of_shifted_lines() {
+        let mut highlighter = Highlighter::default();
+        highlighter.set_language(Some(Language::Rust));
+        let mut text = lines(&["let a;", "let b;"]);
+        highlighter.update(&text, text.len());
+        if let Some(cached) = highlighter.lines[1].as_mut() {
+            cached.annotations.clear();
+        }
+        // 在前面插入一行, 原来的第二行挪到第三行, 缓存跟着挪过去
+        text.insert(0, Line::from("fn f() {}"));
+        highlighter.splice(0, 0, 1);
+        highlighter.update(&text, text.len());
+        assert_eq!(types(&highlighter, 0)[0], AnnotationType::Keyword);
+        assert_eq!(types(&highlighter, 1), [AnnotationType::Keyword]);
+        assert!(types(&highlighter, 2).is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+use crate::location::Location;
+
+// 一次可逆的编辑: 把从line_index开始的before这几行替换成after
+// undo时反过来把after替换回before
+pub struct Edit {
+    pub line_index: usize,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    // 编辑前后光标的位置
+    pub caret_before: Location,
+    pub caret_after: Location,
+}
+
+// 一次undo/redo要处理的所有修改
+struct Entry {
+    // 每次修改都会拿到一个新的id, 用来判断当前内容是否和保存时一致
+    id: usize,
+    edits: Vec<Edit>,
+    // 连续输入的字符可以合并成一次undo
+    mergeable: bool,
+}
+
+pub struct History {
+    undo_stack: Vec<Entry>,
+    redo_stack: Vec<Entry>,
+    next_id: usize,
+    // 保存时栈顶的id, None表示保存时的状态已经回不去了
+    saved_id: Option<usize>,
+    // 在一个group中的修改会被当成一次修改来undo
+    grouping: bool,
+    // group中是否已经有修改了
+    group_started: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            next_id: 1,
+            saved_id: Some(0),
+            grouping: false,
+            group_started: false,
+        }
+    }
+}
+
+impl History {
+    pub fn push(&mut self, edit: Edit, mergeable: bool) {
+        let id = self.next_id;
+        self.next_id = self.next_id.saturating_add(1);
+        // 新的修改会让redo失效
+        if let Some(saved_id) = self.saved_id {
+            if self.redo_stack.iter().any(|entry| entry.id == saved_id) {
+                self.saved_id = None;
+            }
+        }
+        self.redo_stack.clear();
+
+        if self.grouping {
+            if self.group_started {
+                if let Some(last) = self.undo_stack.last_mut() {
+                    last.edits.push(edit);
+                    last.id = id;
+                    return;
+                }
+            }
+            self.group_started = true;
+            self.undo_stack.push(Entry { id, edits: vec![edit], mergeable: false });
+            return;
+        }
+
+        if mergeable {
+            if let Some(last) = self.undo_stack.last_mut() {
+                if let [last_edit] = last.edits.as_mut_slice() {
+                    if last.mergeable && Self::can_merge(last_edit, &edit) {
+                        last_edit.after = edit.after;
+                        last_edit.caret_after = edit.caret_after;
+                        last.id = id;
+                        return;
+                    }
+                }
+            }
+        }
+        self.undo_stack.push(Entry { id, edits: vec![edit], mergeable });
+    }
+
+    pub fn begin_group(&mut self) {
+        self.grouping = true;
+        self.group_started = false;
+    }
+
+    pub fn end_group(&mut self) {
+        self.grouping = false;
+        self.group_started = false;
+    }
+
+    // 只合并同一行上紧挨着的两次输入
+    fn can_merge(last: &Edit, next: &Edit) -> bool {
+        last.caret_after == next.caret_before
+            && last.line_index == next.line_index
+            && last.after.len() == 1
+            && next.before.len() == 1
+            && next.after.len() == 1
+    }
+
+    // 返回的修改要倒着撤销
+    pub fn undo(&mut self) -> Option<&[Edit]> {
+        let entry = self.undo_stack.pop()?;
+        self.redo_stack.push(entry);
+        self.redo_stack.last().map(|entry| entry.edits.as_slice())
+    }
+
+    pub fn redo(&mut self) -> Option<&[Edit]> {
+        let entry = self.redo_stack.pop()?;
+        self.undo_stack.push(entry);
+        self.undo_stack.last().map(|entry| entry.edits.as_slice())
+    }
+
+    // 保存之后的输入不再和之前的合并, 这样undo才能正好回到保存时的状态
+    pub fn mark_saved(&mut self) {
+        if let Some(last) = self.undo_stack.last_mut() {
+            last.mergeable = false;
+        }
+        self.saved_id = Some(self.current_id());
+    }
+
+    // 修改了文件的格式之类的, 不能再通过undo回到保存时的状态
+    pub fn forget_saved(&mut self) {
+        self.saved_id = None;
+    }
+
+    pub fn is_at_saved(&self) -> bool {
+        self.saved_id == Some(self.current_id())
+    }
+
+    fn current_id(&self) -> usize {
+        self.undo_stack.last().map_or(0, |entry| entry.id)
+    }
+}
@@ -0,0 +1,56 @@
+// 缩进的方式
+#[derive(Copy, Clone)]
+pub struct Indentation {
+    // 一级缩进的宽度, 和制表位的宽度一致
+    pub width: usize,
+    // 按Tab时插入空格而不是制表符
+    pub expand_tabs: bool,
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Self {
+            width: 4,
+            expand_tabs: false,
+        }
+    }
+}
+
+impl Indentation {
+    // 一级缩进
+    pub fn unit(self) -> String {
+        if self.expand_tabs {
+            " ".repeat(self.width.max(1))
+        } else {
+            "\t".to_string()
+        }
+    }
+
+    // 在第col列按Tab时插入的内容: 空格补齐到下一个制表位, 或者一个制表符
+    pub fn tab_at(self, col: usize) -> String {
+        if self.expand_tabs {
+            let width = self.width.max(1);
+            " ".repeat(width - col % width)
+        } else {
+            "\t".to_string()
+        }
+    }
+
+    // 减少一级缩进要删掉行首的几个字符: 一个制表符, 或者退回到上一个制表位的空格
+    pub fn dedent_len(self, text: &str) -> usize {
+        if text.starts_with('\t') {
+            return 1;
+        }
+        let spaces = text.chars().take_while(|&ch| ch == ' ').count();
+        if spaces == 0 {
+            return 0;
+        }
+        (spaces - 1) % self.width.max(1) + 1
+    }
+}
+
+// 行首的空白
+pub fn leading_whitespace(text: &str) -> &str {
+    let end = text.find(|ch: char| ch != ' ' && ch != '\t').unwrap_or(text.len());
+    &text[..end]
+}
@@ -0,0 +1,354 @@
+use std::mem;
+
+use crate::editorcommand::Direction;
+use crate::terminal::{Position, Size};
+
+// 每个窗口的编号, 分割出来的新窗口用新的编号
+pub type WindowId = usize;
+
+// 屏幕上的一块矩形区域
+#[derive(Copy, Clone, Default, Eq, PartialEq)]
+pub struct Rect {
+    pub origin: Position,
+    pub size: Size,
+}
+
+impl Rect {
+    pub const fn contains(&self, position: Position) -> bool {
+        position.row >= self.origin.row
+            && position.row < self.origin.row + self.size.height
+            && position.col >= self.origin.col
+            && position.col < self.origin.col + self.size.width
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum SplitDirection {
+    // 上下排列, 每个窗口的状态栏就是分隔线
+    Horizontal,
+    // 左右排列, 中间用一列竖线分隔
+    Vertical,
+}
+
+impl SplitDirection {
+    // 分隔线占的宽度
+    const fn separator(self) -> usize {
+        match self {
+            Self::Horizontal => 0,
+            Self::Vertical => 1,
+        }
+    }
+
+    // 沿着排列的方向量出来的长度
+    const fn extent(self, size: Size) -> usize {
+        match self {
+            Self::Horizontal => size.height,
+            Self::Vertical => size.width,
+        }
+    }
+}
+
+enum Node {
+    Window(WindowId),
+    Split {
+        direction: SplitDirection,
+        // 第一个子节点占多少比例(去掉分隔线之后)
+        ratio: f64,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    fn contains(&self, id: WindowId) -> bool {
+        match self {
+            Self::Window(window) => *window == id,
+            Self::Split { first, second, .. } => first.contains(id) || second.contains(id),
+        }
+    }
+
+    // 最左上的窗口
+    fn first_window(&self) -> WindowId {
+        match self {
+            Self::Window(window) => *window,
+            Self::Split { first, .. } => first.first_window(),
+        }
+    }
+
+    // 把area按照ratio分成两块
+    fn divide(direction: SplitDirection, ratio: f64, area: Rect) -> (Rect, Rect) {
+        let available = direction.extent(area.size).saturating_sub(direction.separator());
+        // 两边都至少留一行/一列
+        let first = if available < 2 {
+            available
+        } else {
+            ((available as f64 * ratio).round() as usize).clamp(1, available - 1)
+        };
+        let second = available - first;
+        let offset = first + direction.separator();
+        match direction {
+            SplitDirection::Horizontal => (
+                Rect { size: Size { height: first, ..area.size }, ..area },
+                Rect {
+                    origin: Position { row: area.origin.row + offset, ..area.origin },
+                    size: Size { height: second, ..area.size },
+                },
+            ),
+            SplitDirection::Vertical => (
+                Rect { size: Size { width: first, ..area.size }, ..area },
+                Rect {
+                    origin: Position { col: area.origin.col + offset, ..area.origin },
+                    size: Size { width: second, ..area.size },
+                },
+            ),
+        }
+    }
+
+    fn arrange(&self, area: Rect, windows: &mut Vec<(WindowId, Rect)>, separators: &mut Vec<Rect>) {
+        match self {
+            Self::Window(id) => windows.push((*id, area)),
+            Self::Split { direction, ratio, first, second } => {
+                let (first_area, second_area) = Self::divide(*direction, *ratio, area);
+                if *direction == SplitDirection::Vertical {
+                    separators.push(Rect {
+                        origin: Position {
+                            col: first_area.origin.col + first_area.size.width,
+                            ..area.origin
+                        },
+                        size: Size { width: 1, ..area.size },
+                    });
+                }
+                first.arrange(first_area, windows, separators);
+                second.arrange(second_area, windows, separators);
+            }
+        }
+    }
+
+    // 调整包含id的最内层的同方向分割, 让id所在的那一边变大delta
+    fn resize(&mut self, id: WindowId, target: SplitDirection, delta: isize, area: Rect) -> bool {
+        let Self::Split { direction, ratio, first, second } = self else {
+            return false;
+        };
+        let in_first = first.contains(id);
+        if !in_first && !second.contains(id) {
+            return false;
+        }
+        let (first_area, second_area) = Self::divide(*direction, *ratio, area);
+        let resized = if in_first {
+            first.resize(id, target, delta, first_area)
+        } else {
+            second.resize(id, target, delta, second_area)
+        };
+        if resized || *direction != target {
+            return resized;
+        }
+        let available = direction.extent(area.size).saturating_sub(direction.separator());
+        if available >= 2 {
+            let step = delta as f64 / available as f64;
+            let min = 1.0 / available as f64;
+            *ratio = if in_first { *ratio + step } else { *ratio - step }.clamp(min, 1.0 - min);
+        }
+        true
+    }
+
+    // 去掉id这个窗口, 它的兄弟节点占据原来父节点的位置, 返回兄弟节点中的一个窗口
+    fn remove(&mut self, id: WindowId) -> Option<WindowId> {
+        let Self::Split { first, second, .. } = self else {
+            return None;
+        };
+        let remaining = if matches!(**first, Self::Window(window) if window == id) {
+            mem::replace(&mut **second, Self::Window(id))
+        } else if matches!(**second, Self::Window(window) if window == id) {
+            mem::replace(&mut **first, Self::Window(id))
+        } else {
+            return first.remove(id).or_else(|| second.remove(id));
+        };
+        let focus = remaining.first_window();
+        *self = remaining;
+        Some(focus)
+    }
+
+    fn split(&mut self, id: WindowId, new: WindowId, direction: SplitDirection) -> bool {
+        match self {
+            Self::Window(window) if *window == id => {
+                *self = Self::Split {
+                    direction,
+                    ratio: 0.5,
+                    first: Box::new(Self::Window(id)),
+                    second: Box::new(Self::Window(new)),
+                };
+                true
+            }
+            Self::Window(_) => false,
+            Self::Split { first, second, .. } => first.split(id, new, direction) || second.split(id, new, direction),
+        }
+    }
+}
+
+// 窗口的布局: 一棵二叉树, 叶子是窗口, 中间的节点把区域上下或者左右分成两块
+pub struct Layout {
+    root: Node,
+}
+
+impl Layout {
+    pub const fn new(id: WindowId) -> Self {
+        Self {
+            root: Node::Window(id),
+        }
+    }
+
+    // 把id这个窗口一分为二, 新窗口在下面或者右边
+    pub fn split(&mut self, id: WindowId, new: WindowId, direction: SplitDirection) {
+        self.root.split(id, new, direction);
+    }
+
+    // 关闭id这个窗口, 返回接下来获得焦点的窗口; 只剩这一个窗口时不能关闭, 返回None
+    pub fn remove(&mut self, id: WindowId) -> Option<WindowId> {
+        self.root.remove(id)
+    }
+
+    // 每个窗口在area中的位置, 以及左右排列的窗口之间的竖线
+    pub fn arrange(&self, area: Rect) -> (Vec<(WindowId, Rect)>, Vec<Rect>) {
+        let mut windows = Vec::new();
+        let mut separators = Vec::new();
+        self.root.arrange(area, &mut windows, &mut separators);
+        (windows, separators)
+    }
+
+    // 沿着direction的方向把id这个窗口放大delta行/列(负数是缩小), 旁边的窗口相应地缩小
+    // 没有这个方向的分割时返回false
+    pub fn resize(&mut self, id: WindowId, direction: SplitDirection, delta: isize, area: Rect) -> bool {
+        self.root.resize(id, direction, delta, area)
+    }
+}
+
+// 从from这个区域往dir方向看, 紧挨着的窗口里离得最近的一个
+pub fn neighbor(windows: &[(WindowId, Rect)], from: Rect, dir: Direction) -> Option<WindowId> {
+    let overlaps = |start: usize, len: usize, other_start: usize, other_len: usize| {
+        start < other_start + other_len && other_start < start + len
+    };
+    windows
+        .iter()
+        .filter(|(_, rect)| match dir {
+            Direction::Up => {
+                rect.origin.row + rect.size.height <= from.origin.row
+                    && overlaps(rect.origin.col, rect.size.width, from.origin.col, from.size.width)
+            }
+            Direction::Down => {
+                rect.origin.row >= from.origin.row + from.size.height
+                    && overlaps(rect.origin.col, rect.size.width, from.origin.col, from.size.width)
+            }
+            Direction::Left => {
+                rect.origin.col + rect.size.width <= from.origin.col
+                    && overlaps(rect.origin.row, rect.size.height, from.origin.row, from.size.height)
+            }
+            Direction::Right => {
+                rect.origin.col >= from.origin.col + from.size.width
+                    && overlaps(rect.origin.row, rect.size.height, from.origin.row, from.size.height)
+            }
+            _ => false,
+        })
+        // 先比较隔了多远, 再比较和from的左上角对得齐不齐
+        .min_by_key(|(_, rect)| {
+            let distance = match dir {
+                Direction::Up => from.origin.row - (rect.origin.row + rect.size.height),
+                Direction::Down => rect.origin.row - (from.origin.row + from.size.height),
+                Direction::Left => from.origin.col - (rect.origin.col + rect.size.width),
+                _ => rect.origin.col - (from.origin.col + from.size.width),
+            };
+            (distance, rect.origin.row.abs_diff(from.origin.row) + rect.origin.col.abs_diff(from.origin.col))
+        })
+        .map(|(id, _)| *id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Layout, Rect, SplitDirection, WindowId, neighbor};
+    use crate::editorcommand::Direction;
+    use crate::terminal::{Position, Size};
+
+    const AREA: Rect = Rect {
+        origin: Position { row: 0, col: 0 },
+        size: Size { height: 20, width: 41 },
+    };
+
+    // (编号, 行, 列, 高, 宽)
+    fn windows(layout: &Layout) -> Vec<(WindowId, usize, usize, usize, usize)> {
+        layout
+            .arrange(AREA)
+            .0
+            .into_iter()
+            .map(|(id, rect)| (id, rect.origin.row, rect.origin.col, rect.size.height, rect.size.width))
+            .collect()
+    }
+
+    fn find(layout: &Layout, id: WindowId, dir: Direction) -> Option<WindowId> {
+        let (windows, _) = layout.arrange(AREA);
+        let from = windows.iter().find(|(window, _)| *window == id)?.1;
+        neighbor(&windows, from, dir)
+    }
+
+    #[test]
+    fn split_divides_the_area() {
+        let mut layout = Layout::new(0);
+        layout.split(0, 1, SplitDirection::Vertical);
+        layout.split(1, 2, SplitDirection::Horizontal);
+        assert_eq!(windows(&layout), [(0, 0, 0, 20, 20), (1, 0, 21, 10, 20), (2, 10, 21, 10, 20)]);
+        let (_, separators) = layout.arrange(AREA);
+        assert_eq!(separators.len(), 1);
+        assert_eq!(separators[0].origin.col, 20);
+    }
+
+    #[test]
+    fn closing_the_last_child_collapses_its_parent() {
+        let mut layout = Layout::new(0);
+        layout.split(0, 1, SplitDirection::Vertical);
+        layout.split(1, 2, SplitDirection::Horizontal);
+        assert_eq!(layout.remove(2), Some(1));
+        assert_eq!(windows(&layout), [(0, 0, 0, 20, 20), (1, 0, 21, 20, 20)]);
+        assert_eq!(layout.remove(0), Some(1));
+        assert_eq!(windows(&layout), [(1, 0, 0, 20, 41)]);
+        // 只剩一个窗口时不能关闭
+        assert_eq!(layout.remove(1), None);
+        assert_eq!(windows(&layout), [(1, 0, 0, 20, 41)]);
+    }
+
+    #[test]
+    fn focus_moves_to_the_geometric_neighbor() {
+        // 0 2
+        // 1 3
+        let mut layout = Layout::new(0);
+        layout.split(0, 1, SplitDirection::Horizontal);
+        layout.split(0, 2, SplitDirection::Vertical);
+        layout.split(1, 3, SplitDirection::Vertical);
+        assert_eq!(find(&layout, 2, Direction::Down), Some(3));
+        assert_eq!(find(&layout, 3, Direction::Left), Some(1));
+        assert_eq!(find(&layout, 1, Direction::Up), Some(0));
+        assert_eq!(find(&layout, 0, Direction::Right), Some(2));
+        assert_eq!(find(&layout, 0, Direction::Left), None);
+        assert_eq!(find(&layout, 3, Direction::Down), None);
+    }
+
+    #[test]
+    fn neighbor_prefers_the_one_aligned_with_the_top_left() {
+        let mut layout = Layout::new(0);
+        layout.split(0, 1, SplitDirection::Vertical);
+        layout.split(1, 2, SplitDirection::Horizontal);
+        assert_eq!(find(&layout, 0, Direction::Right), Some(1));
+        assert_eq!(find(&layout, 2, Direction::Left), Some(0));
+    }
+
+    #[test]
+    fn resize_stops_at_the_minimum_size() {
+        let mut layout = Layout::new(0);
+        layout.split(0, 1, SplitDirection::Vertical);
+        assert!(layout.resize(0, SplitDirection::Vertical, 5, AREA));
+        assert_eq!(windows(&layout), [(0, 0, 0, 20, 25), (1, 0, 26, 20, 15)]);
+        assert!(layout.resize(0, SplitDirection::Vertical, 100, AREA));
+        assert_eq!(windows(&layout), [(0, 0, 0, 20, 39), (1, 0, 40, 20, 1)]);
+        assert!(layout.resize(1, SplitDirection::Vertical, 100, AREA));
+        assert_eq!(windows(&layout), [(0, 0, 0, 20, 1), (1, 0, 2, 20, 39)]);
+        // 没有上下的分割
+        assert!(!layout.resize(0, SplitDirection::Horizontal, 1, AREA));
+    }
+}
@@ -1,20 +1,34 @@
+use std::cell::Cell;
 use std::fmt;
 use std::ops::Range;
 
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
+use crate::annotation::{AnnotatedText, Annotation};
+
 #[derive(Copy, Clone)]
 enum GraphemeWidth {
     Half,
     Full,
+    // 制表符, 宽度取决于所在的列
+    Tab,
+}
+
+// 制表符的显示方式
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct TabStops {
+    // 每隔多少列一个制表位
+    pub width: usize,
+    // 是否用箭头把制表符标出来
+    pub visible: bool,
 }
 
-impl GraphemeWidth {
-    const fn saturating_add(self, other: usize) -> usize {
-        match self {
-            Self::Half => other.saturating_add(1),
-            Self::Full => other.saturating_add(2),
+impl Default for TabStops {
+    fn default() -> Self {
+        Self {
+            width: 4,
+            visible: false,
         }
     }
 }
@@ -28,15 +42,35 @@ struct TextFragment {
     replacement: Option<char>,
 }
 
+impl TextFragment {
+    // 从第col列开始显示时占几列, 制表符一直延伸到下一个制表位
+    fn width_at(&self, col: usize, tabs: TabStops) -> usize {
+        match self.rendered_width {
+            GraphemeWidth::Half => 1,
+            GraphemeWidth::Full => 2,
+            GraphemeWidth::Tab => {
+                let tab_width = tabs.width.max(1);
+                tab_width - col % tab_width
+            }
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Line {
     fragments: Vec<TextFragment>,
+    // 上一次软换行用的宽度, 制表符设置和分成的屏幕行数
+    // Line创建之后不会再修改, 编辑时整行会换成新的Line, 缓存也就跟着丢掉了
+    wrapped_rows: Cell<Option<(usize, TabStops, usize)>>,
 }
 
 impl From<&str> for Line {
     fn from(line: &str) -> Self {
         let fragments = Self::str_to_fragments(line);
-        Self { fragments }
+        Self {
+            fragments,
+            wrapped_rows: Cell::new(None),
+        }
     }
 }
 
@@ -53,23 +87,64 @@ impl fmt::Display for Line {
 
 impl Line {
     // range 是 position的col
-    pub fn get_visible_graphemes(&self, range: Range<usize>) -> String {
+    pub fn get_visible_graphemes(&self, range: Range<usize>, tabs: TabStops) -> String {
+        self.get_annotated_visible_graphemes(range, &[], tabs)
+            .into_iter()
+            .map(|segment| segment.text)
+            .collect()
+    }
+
+    // 和get_visible_graphemes一样, 但是按annotations把结果分成样式不同的多段
+    pub fn get_annotated_visible_graphemes(
+        &self,
+        range: Range<usize>,
+        annotations: &[Annotation],
+        tabs: TabStops,
+    ) -> Vec<AnnotatedText> {
+        let mut segments: Vec<AnnotatedText> = Vec::new();
         if range.start >= range.end {
-            return String::new();
+            return segments;
         }
-        let mut result = String::new();
         // 指向要被加入到result的grapheme的开头
         let mut current_pos = 0;
-        for fragment in &self.fragments {
+        for (index, fragment) in self.fragments.iter().enumerate() {
             if current_pos >= range.end {
                 break;
             }
 
             // 指向下一个grapheme的开头
-            let fragment_end = fragment.rendered_width.saturating_add(current_pos);
+            let fragment_end = current_pos.saturating_add(fragment.width_at(current_pos, tabs));
             // 判断这个grapheme能不能放的下去
             if fragment_end > range.start {
-                if fragment_end > range.end || current_pos < range.start {
+                // 后面的annotation优先
+                let annotation_type = annotations
+                    .iter()
+                    .rev()
+                    .find(|annotation| annotation.range.contains(&index))
+                    .map(|annotation| annotation.annotation_type);
+                // 样式变了就开始新的一段
+                if segments.last().is_none_or(|segment| segment.annotation_type != annotation_type) {
+                    segments.push(AnnotatedText {
+                        text: String::new(),
+                        annotation_type,
+                    });
+                }
+                let Some(AnnotatedText { text: result, .. }) = segments.last_mut() else {
+                    break;
+                };
+
+                if let GraphemeWidth::Tab = fragment.rendered_width {
+                    // 制表符被截断时只画能看到的部分
+                    let visible_width = fragment_end.min(range.end).saturating_sub(current_pos.max(range.start));
+                    let show_arrow = tabs.visible && current_pos >= range.start;
+                    let spaces = if show_arrow {
+                        result.push('→');
+                        visible_width.saturating_sub(1)
+                    } else {
+                        visible_width
+                    };
+                    result.push_str(&" ".repeat(spaces));
+                } else if fragment_end > range.end || current_pos < range.start {
                     // Clip on the right or left
                     result.push('⋯');
                 } else if let Some(char) = fragment.replacement {
@@ -80,68 +155,166 @@ impl Line {
             }
             current_pos = fragment_end;
         }
-        result
+        segments
     }
 
     // 计算grapheme_index前字符串的总宽度
-    pub fn width_until(&self, grapheme_index: usize) -> usize {
+    pub fn width_until(&self, grapheme_index: usize, tabs: TabStops) -> usize {
         self.fragments
             .iter()
             .take(grapheme_index) // 只会获取 grapheme_index 个元素，而忽略后续的元素
-            .map(|fragment| match fragment.rendered_width {
-                GraphemeWidth::Half => 1,
-                GraphemeWidth::Full => 2,
-            })
-            .sum()
+            .fold(0, |col, fragment| col.saturating_add(fragment.width_at(col, tabs)))
     }
 
-    pub fn grapheme_count(&self) -> usize {
+    // width_until的逆运算: 屏幕上第col列是哪个grapheme, 超过行尾时返回grapheme_count
+    // 宽字符和制表符占多列, 点在哪一列都算这个grapheme
+    pub fn grapheme_index_at(&self, col: usize, tabs: TabStops) -> usize {
+        let mut current_pos: usize = 0;
+        for (index, fragment) in self.fragments.iter().enumerate() {
+            current_pos = current_pos.saturating_add(fragment.width_at(current_pos, tabs));
+            if current_pos > col {
+                return index;
+            }
+        }
         self.fragments.len()
     }
 
-    pub fn insert_char(&mut self, character: char, grapheme_index: usize) {
-        let mut result = String::new();
-        for (index, fragment) in self.fragments.iter().enumerate() {
-            if index == grapheme_index {
-                result.push(character);
+    // 软换行: 把这一行按屏幕宽度分成多段, 返回每段的grapheme下标范围, 至少有一段
+    // 尽量在空白之后断开, 一个单词比一整行还长时才从中间断开
+    // 宽字符放不下时整个移到下一段
+    // 制表符的宽度按它在整行中的列计算, 和width_until保持一致
+    pub fn wrap(&self, width: usize, tabs: TabStops) -> Vec<Range<usize>> {
+        let mut rows = Vec::new();
+        let mut start = 0;
+        // 这一段开头和当前所在的列
+        let mut start_col = 0;
+        let mut col: usize = 0;
+        // 这一段里最后一个空白之后的位置
+        let mut break_at = None;
+        let mut index = 0;
+        while let Some(fragment) = self.fragments.get(index) {
+            let fragment_end = col.saturating_add(fragment.width_at(col, tabs));
+            // 每段至少放一个grapheme, 否则宽度太小时会死循环
+            if fragment_end.saturating_sub(start_col) > width && index > start {
+                let end = break_at.filter(|&end| end > start).unwrap_or(index);
+                rows.push(start..end);
+                // 从断开的位置重新计算宽度
+                start = end;
+                index = end;
+                start_col = self.width_until(end, tabs);
+                col = start_col;
+                break_at = None;
+                continue;
+            }
+            col = fragment_end;
+            index += 1;
+            if fragment.grapheme.trim().is_empty() {
+                break_at = Some(index);
             }
-            result.push_str(&fragment.grapheme);
-        }
-        if grapheme_index >= self.fragments.len() {
-            result.push(character);
         }
-        self.fragments = Self::str_to_fragments(&result);
+        rows.push(start..self.fragments.len());
+        rows
     }
 
-    pub fn delete(&mut self, grapheme_index: usize) {
-        let mut result = String::new();
-        for (index, fragment) in self.fragments.iter().enumerate() {
-            if index != grapheme_index {
-                result.push_str(&fragment.grapheme);
+    // 软换行之后占几个屏幕行, 宽度和制表符设置都没变时直接用上次的结果
+    pub fn wrapped_row_count(&self, width: usize, tabs: TabStops) -> usize {
+        if let Some((cached_width, cached_tabs, count)) = self.wrapped_rows.get() {
+            if cached_width == width && cached_tabs == tabs {
+                return count;
             }
         }
-        self.fragments = Self::str_to_fragments(&result);
+        let count = self.wrap(width, tabs).len();
+        self.wrapped_rows.set(Some((width, tabs, count)));
+        count
     }
 
-    pub fn append(&mut self, other: &Self) {
-        let mut concat = self.to_string();
-        concat.push_str(&other.to_string());
-        self.fragments = Self::str_to_fragments(&concat);
+    // 包含grapheme_index的单词的范围, 连续的空白或者标点也当成一个单词
+    pub fn word_range_at(&self, grapheme_index: usize) -> Range<usize> {
+        let Some(fragment) = self.fragments.get(grapheme_index) else {
+            return grapheme_index..grapheme_index;
+        };
+        let class = Self::char_class(&fragment.grapheme);
+        let is_same = |fragment: &TextFragment| Self::char_class(&fragment.grapheme) == class;
+        let start = self.fragments[..grapheme_index]
+            .iter()
+            .rposition(|fragment| !is_same(fragment))
+            .map_or(0, |index| index + 1);
+        let end = self.fragments[grapheme_index..]
+            .iter()
+            .position(|fragment| !is_same(fragment))
+            .map_or(self.fragments.len(), |index| grapheme_index + index);
+        start..end
     }
 
-    pub fn split(&mut self, at: usize) -> Self {
-        if at > self.fragments.len() {
-            return Self::default();
+    // 0: 空白, 1: 单词, 2: 标点
+    fn char_class(grapheme: &str) -> u8 {
+        if grapheme.trim().is_empty() {
+            0
+        } else if grapheme.chars().all(|ch| ch.is_alphanumeric() || ch == '_') {
+            1
+        } else {
+            2
         }
-        let remainder = self.fragments.split_off(at);
-        Self {
-            fragments: remainder,
+    }
+
+    pub fn grapheme_count(&self) -> usize {
+        self.fragments.len()
+    }
+
+    // 取出range(grapheme下标)范围内的文本
+    pub fn substr(&self, range: Range<usize>) -> String {
+        self.fragments
+            .iter()
+            .skip(range.start)
+            .take(range.end.saturating_sub(range.start))
+            .map(|fragment| fragment.grapheme.as_str())
+            .collect()
+    }
+
+    // 找出query在这一行中出现的所有位置(grapheme下标)
+    // 只有开头和结尾都落在grapheme边界上才算匹配, 这样不会匹配到半个字符
+    pub fn find_all(&self, query: &str) -> Vec<Range<usize>> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let text = self.to_string();
+        let offsets = self.byte_offsets();
+        offsets
+            .iter()
+            .enumerate()
+            .take(self.fragments.len())
+            .filter(|(_, &start)| text[start..].starts_with(query))
+            .filter_map(|(start_index, &start)| {
+                offsets
+                    .binary_search(&(start + query.len()))
+                    .ok()
+                    .map(|end_index| start_index..end_index)
+            })
+            .collect()
+    }
+
+    // 每个grapheme开头的字节下标, 最后再加上整行的长度
+    pub fn byte_offsets(&self) -> Vec<usize> {
+        let mut offsets = Vec::with_capacity(self.fragments.len() + 1);
+        let mut offset = 0;
+        for fragment in &self.fragments {
+            offsets.push(offset);
+            offset += fragment.grapheme.len();
         }
+        offsets.push(offset);
+        offsets
     }
 
     fn str_to_fragments(line_str: &str) -> Vec<TextFragment> {
         line_str.graphemes(true)
             .map(|grapheme| {
+                if grapheme == "\t" {
+                    return TextFragment {
+                        grapheme: grapheme.to_string(),
+                        rendered_width: GraphemeWidth::Tab,
+                        replacement: None,
+                    };
+                }
                 let (replacement, rendered_width) = Self::replacement_character(grapheme)
                     .map_or_else(
                         || {
@@ -166,20 +339,114 @@ impl Line {
 
     fn replacement_character(for_str: &str) -> Option<char> {
         let width = for_str.width();
+        let mut chars = for_str.chars();
+        // 单个的控制字符(比如二进制文件中的字节), 不管宽度是多少都不能原样输出到终端
+        let is_control = chars.next().is_some_and(char::is_control) && chars.next().is_none();
         match for_str {
             " " => None,
-            "\t" => Some(' '),
+            _ if is_control => Some('▯'),
             _ if width > 0 && for_str.trim().is_empty() => Some('␣'),
-            _ if width == 0 => {
-                let mut chars = for_str.chars();
-                if let Some(ch) = chars.next() {
-                    if ch.is_control() && chars.next().is_none() {
-                        return Some('▯');
-                    }
-                }
-                Some('·')
-            }
+            _ if width == 0 => Some('·'),
             _ => None,
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::{Line, TabStops};
+
+    const TABS: TabStops = TabStops { width: 4, visible: false };
+
+    // 每段的开头和结尾
+    fn wrap(text: &str, width: usize) -> Vec<(usize, usize)> {
+        Line::from(text)
+            .wrap(width, TABS)
+            .into_iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    #[test]
+    fn wrap_breaks_after_whitespace() {
+        assert_eq!(wrap("hello world", 7), [(0, 6), (6, 11)]);
+        assert_eq!(wrap("hello world", 20), [(0, 11)]);
+        assert_eq!(wrap("", 5), [(0, 0)]);
+    }
+
+    #[test]
+    fn wrap_splits_a_word_longer_than_the_width() {
+        assert_eq!(wrap("abcdefghij", 4), [(0, 4), (4, 8), (8, 10)]);
+        assert_eq!(wrap("ab abcdefghij", 4), [(0, 3), (3, 7), (7, 11), (11, 13)]);
+    }
+
+    #[test]
+    fn wrap_moves_a_wide_char_that_does_not_fit() {
+        assert_eq!(wrap("ab中", 3), [(0, 2), (2, 3)]);
+        assert_eq!(wrap("ab中", 4), [(0, 3)]);
+    }
+
+    #[test]
+    fn wrap_measures_a_tab_from_its_column() {
+        // 第二段从第5列开始, 制表符只到第8列, 占3列而不是4列
+        assert_eq!(wrap("abcde\tfg", 5), [(0, 5), (5, 8)]);
+        assert_eq!(wrap("ab\tc", 5), [(0, 4)]);
+    }
+
+    #[test]
+    fn wrap_to_width_one() {
+        // 宽字符比一行还宽, 也要单独占一段
+        assert_eq!(wrap("a中b", 1), [(0, 1), (1, 2), (2, 3)]);
+        assert_eq!(Line::from("a中b").wrapped_row_count(1, TABS), 3);
+    }
+
+    #[test]
+    fn tab_extends_to_the_next_stop() {
+        let tab = &Line::from("\t").fragments[0];
+        assert_eq!(tab.width_at(0, TABS), 4);
+        assert_eq!(tab.width_at(1, TABS), 3);
+        assert_eq!(tab.width_at(3, TABS), 1);
+        assert_eq!(tab.width_at(4, TABS), 4);
+        let eight = TabStops { width: 8, ..TABS };
+        assert_eq!(tab.width_at(0, eight), 8);
+        assert_eq!(tab.width_at(5, eight), 3);
+    }
+
+    #[test]
+    fn width_until_counts_tabs_from_their_column() {
+        let line = Line::from("ab\tc");
+        assert_eq!(line.width_until(3, TABS), 4);
+        assert_eq!(line.width_until(4, TABS), 5);
+        assert_eq!(line.width_until(3, TabStops { width: 8, ..TABS }), 8);
+        // 制表符从两个制表位中间开始
+        assert_eq!(Line::from("abcde\t").width_until(6, TABS), 8);
+    }
+
+    #[test]
+    fn grapheme_index_at_inside_a_tab() {
+        let line = Line::from("a\tb");
+        let indexes: Vec<usize> = (0..6).map(|col| line.grapheme_index_at(col, TABS)).collect();
+        assert_eq!(indexes, [0, 1, 1, 1, 2, 3]);
+        let eight = TabStops { width: 8, ..TABS };
+        assert_eq!(line.grapheme_index_at(7, eight), 1);
+        assert_eq!(line.grapheme_index_at(8, eight), 2);
+    }
+
+    #[test]
+    fn clipped_tab_shows_only_its_visible_part() {
+        let line = Line::from("a\tb");
+        assert_eq!(line.get_visible_graphemes(2..6, TABS), "  b");
+        assert_eq!(line.get_visible_graphemes(0..3, TABS), "a  ");
+        // 开头被截掉的制表符不画箭头
+        let visible = TabStops { visible: true, ..TABS };
+        assert_eq!(line.get_visible_graphemes(2..6, visible), "  b");
+        assert_eq!(line.get_visible_graphemes(0..3, visible), "a→ ");
+    }
+
+    #[test]
+    fn visible_tab_is_drawn_as_an_arrow() {
+        let visible = TabStops { visible: true, ..TABS };
+        assert_eq!(Line::from("\tx").get_visible_graphemes(0..10, visible), "→   x");
+        assert_eq!(Line::from("ab\tx").get_visible_graphemes(0..10, visible), "ab→ x");
+        assert_eq!(Line::from("\tx").get_visible_graphemes(0..10, TABS), "    x");
+    }
+}
@@ -9,8 +9,9 @@
 //     pub y: usize,
 // }
 
-#[derive(Copy, Clone, Default)]
+// 字段顺序决定了比较顺序: 先比较行, 再比较行内的grapheme
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Location {
-    pub grapheme_index: usize,
     pub line_index: usize,
+    pub grapheme_index: usize,
 }
\ No newline at end of file
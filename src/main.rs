@@ -1,11 +1,11 @@
-use std::io::Read;
-
 use crate::editor::Editor;
 
 mod editor;
 mod terminal;
 mod view;
 mod buffer;
+mod bufferlist;
+mod bufferpicker;
 mod editorcommand;
 mod location;
 mod line;
@@ -13,6 +13,25 @@ mod uicomponent;
 mod statusbar;
 mod fileinfo;
 mod documentstatus;
+mod history;
+mod commandbar;
+mod messagebar;
+mod config;
+mod atomicwrite;
+mod encoding;
+mod clipboard;
+mod gutter;
+mod indent;
+mod annotation;
+mod syntax;
+mod highlighter;
+mod style;
+mod theme;
+mod screen;
+mod layout;
+mod window;
+#[cfg(test)]
+mod memorybackend;
 
 fn main() {
     Editor::new().unwrap().run();
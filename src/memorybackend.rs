@@ -0,0 +1,146 @@
+use std::io::Error;
+
+use crate::screen::Cell;
+use crate::terminal::{Backend, Position, Size};
+
+// 把画面记录在内存里的终端, 用来在没有TTY的环境下测试
+pub struct MemoryBackend {
+    size: Size,
+    cells: Vec<Vec<Cell>>,
+    caret: Position,
+    caret_visible: bool,
+    title: String,
+    clipboard: Option<String>,
+    // 一共输出过多少个格子, 用来检查是不是只画了变化的部分
+    cells_drawn: usize,
+}
+
+impl MemoryBackend {
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            cells: vec![vec![Cell::default(); size.width]; size.height],
+            caret: Position::default(),
+            caret_visible: true,
+            title: String::new(),
+            clipboard: None,
+            cells_drawn: 0,
+        }
+    }
+
+    pub fn cell(&self, position: Position) -> Option<&Cell> {
+        self.cells.get(position.row)?.get(position.col)
+    }
+
+    // 一行的文本, 去掉了末尾的空白
+    pub fn row_text(&self, row: usize) -> String {
+        self.cells
+            .get(row)
+            .map(|cells| cells.iter().map(|cell| cell.symbol.as_str()).collect::<String>())
+            .unwrap_or_default()
+            .trim_end()
+            .to_string()
+    }
+
+    // 整个屏幕的文本, 每行一个字符串
+    pub fn screen(&self) -> Vec<String> {
+        (0..self.size.height).map(|row| self.row_text(row)).collect()
+    }
+
+    fn set_cell(&mut self, position: Position, cell: Cell) {
+        if let Some(target) = self.cells.get_mut(position.row).and_then(|row| row.get_mut(position.col)) {
+            *target = cell;
+        }
+    }
+
+    pub fn caret(&self) -> Option<Position> {
+        self.caret_visible.then_some(self.caret)
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn clipboard(&self) -> Option<&str> {
+        self.clipboard.as_deref()
+    }
+
+    pub const fn cells_drawn(&self) -> usize {
+        self.cells_drawn
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn initialize(&mut self) -> Result<(), Error> {
+        self.clear_screen()
+    }
+
+    fn terminate(&mut self) -> Result<(), Error> {
+        self.caret_visible = true;
+        Ok(())
+    }
+
+    fn size(&self) -> Result<Size, Error> {
+        Ok(self.size)
+    }
+
+    fn clear_screen(&mut self) -> Result<(), Error> {
+        for row in &mut self.cells {
+            row.fill(Cell::default());
+        }
+        Ok(())
+    }
+
+    fn move_caret_to(&mut self, position: Position) -> Result<(), Error> {
+        self.caret = position;
+        Ok(())
+    }
+
+    fn hide_caret(&mut self) -> Result<(), Error> {
+        self.caret_visible = false;
+        Ok(())
+    }
+
+    fn show_caret(&mut self) -> Result<(), Error> {
+        self.caret_visible = true;
+        Ok(())
+    }
+
+    // 和真实的终端一样, 宽字符的后半个格子跟着前半个一起画
+    fn draw(&mut self, changes: &[(Position, &Cell)]) -> Result<(), Error> {
+        for &(position, cell) in changes {
+            self.set_cell(position, cell.clone());
+            if cell.width() == 2 {
+                let continuation = Cell {
+                    symbol: String::new(),
+                    style: cell.style,
+                };
+                self.set_cell(Position { col: position.col + 1, ..position }, continuation);
+            }
+            self.caret = Position {
+                col: position.col + cell.width(),
+                ..position
+            };
+        }
+        self.cells_drawn += changes.len();
+        Ok(())
+    }
+
+    fn print(&mut self, _text: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        self.title = title.to_string();
+        Ok(())
+    }
+
+    fn set_clipboard(&mut self, text: &str) -> Result<(), Error> {
+        self.clipboard = Some(text.to_string());
+        Ok(())
+    }
+
+    fn execute(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
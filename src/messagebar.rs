@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::commandbar::CommandBar;
+use crate::editorcommand::{Direction, EditorCommand};
+use crate::screen::Screen;
+use crate::terminal::{Position, Size};
+use crate::uicomponent::UIComponent;
+
+// 消息显示多久之后消失
+const DEFAULT_DURATION: Duration = Duration::from_secs(5);
+
+struct Message {
+    text: String,
+    time: Instant,
+}
+
+impl Default for Message {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            time: Instant::now(),
+        }
+    }
+}
+
+impl Message {
+    fn is_expired(&self) -> bool {
+        Instant::now().duration_since(self.time) > DEFAULT_DURATION
+    }
+}
+
+// 提示模式下处理完一个命令的结果
+pub enum PromptResult {
+    // 输入框的内容变了
+    Changed(String),
+    // 按下了Enter
+    Submitted(String),
+    // 按下了Esc
+    Cancelled,
+    // 内容没有变化, 比如移动了光标
+    Unchanged,
+}
+
+// 每个提示的输入历史, 以及正在浏览的位置
+#[derive(Default)]
+struct PromptHistory {
+    entries: Vec<String>,
+    // 正在浏览的历史下标, None表示没有在浏览
+    index: Option<usize>,
+    // 开始浏览历史之前输入的内容
+    draft: String,
+}
+
+// 屏幕最后一行: 平时显示临时消息, 需要用户输入时切换成输入框
+#[derive(Default)]
+pub struct MessageBar {
+    current_message: Message,
+    // 消息过期之后只需要清除一次
+    cleared_after_expiry: bool,
+    command_bar: CommandBar,
+    // 当前的提示, None表示不在提示模式
+    prompt: Option<String>,
+    // 以提示的内容区分不同的历史
+    histories: HashMap<String, PromptHistory>,
+    needs_redraw: bool,
+    size: Size,
+}
+
+impl MessageBar {
+    pub fn update_message(&mut self, new_message: &str) {
+        self.current_message = Message {
+            text: new_message.to_string(),
+            time: Instant::now(),
+        };
+        self.cleared_after_expiry = false;
+        self.mark_redraw(true);
+    }
+
+    // 距离当前消息过期还有多久, 没有需要清除的消息时返回None
+    pub fn time_until_expiry(&self) -> Option<Duration> {
+        if self.cleared_after_expiry || self.is_prompting() {
+            return None;
+        }
+        Some(DEFAULT_DURATION.saturating_sub(Instant::now().duration_since(self.current_message.time)))
+    }
+
+    pub fn is_prompting(&self) -> bool {
+        self.prompt.is_some()
+    }
+
+    pub fn start_prompt(&mut self, prompt: &str) {
+        self.prompt = Some(prompt.to_string());
+        self.command_bar.set_prompt(prompt);
+        self.command_bar.clear_value();
+        let history = self.histories.entry(prompt.to_string()).or_default();
+        history.index = None;
+        self.mark_redraw(true);
+    }
+
+    pub fn end_prompt(&mut self) {
+        self.prompt = None;
+        self.mark_redraw(true);
+    }
+
+    pub fn value(&self) -> String {
+        self.command_bar.value()
+    }
+
+    // 处理提示模式下的命令: Enter提交, Esc取消, Up/Down浏览历史, 其他的交给输入框
+    pub fn handle_prompt_command(&mut self, cmd: EditorCommand) -> PromptResult {
+        let Some(prompt) = self.prompt.clone() else {
+            return PromptResult::Unchanged;
+        };
+        self.mark_redraw(true);
+        match cmd {
+            EditorCommand::Enter => {
+                let value = self.value();
+                self.record_history(&prompt, &value);
+                PromptResult::Submitted(value)
+            }
+            EditorCommand::Dismiss => PromptResult::Cancelled,
+            EditorCommand::Move(Direction::Up) => self.browse_history(&prompt, true),
+            EditorCommand::Move(Direction::Down) => self.browse_history(&prompt, false),
+            _ => {
+                let old_value = self.value();
+                self.command_bar.handle_command(cmd);
+                let value = self.value();
+                if value == old_value {
+                    PromptResult::Unchanged
+                } else {
+                    if let Some(history) = self.histories.get_mut(&prompt) {
+                        history.index = None;
+                    }
+                    PromptResult::Changed(value)
+                }
+            }
+        }
+    }
+
+    fn record_history(&mut self, prompt: &str, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        let history = self.histories.entry(prompt.to_string()).or_default();
+        // 重复的内容只保留最新的一条
+        history.entries.retain(|entry| entry != value);
+        history.entries.push(value.to_string());
+        history.index = None;
+    }
+
+    // older为true时往更早的历史走
+    fn browse_history(&mut self, prompt: &str, older: bool) -> PromptResult {
+        let current = self.value();
+        let Some(history) = self.histories.get_mut(prompt) else {
+            return PromptResult::Unchanged;
+        };
+        let len = history.entries.len();
+        let index = match (history.index, older) {
+            (None, true) if len > 0 => {
+                history.draft = current.clone();
+                Some(len - 1)
+            }
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < len => Some(index + 1),
+            // 走到最新的历史之后, 回到浏览之前输入的内容
+            (Some(_), false) => None,
+            (None, _) => return PromptResult::Unchanged,
+        };
+        history.index = index;
+        let value = index.map_or_else(|| history.draft.clone(), |index| history.entries[index].clone());
+        if value == current {
+            return PromptResult::Unchanged;
+        }
+        self.command_bar.set_value(&value);
+        PromptResult::Changed(value)
+    }
+
+    // 提示模式下光标所在的列
+    pub fn caret_position_col(&self) -> usize {
+        self.command_bar.caret_position_col()
+    }
+}
+
+impl UIComponent for MessageBar {
+    fn mark_redraw(&mut self, value: bool) {
+        self.needs_redraw = value;
+    }
+
+    fn needs_redraw(&self) -> bool {
+        // 消息刚过期的时候也要重绘一次, 把它清掉
+        (!self.cleared_after_expiry && self.current_message.is_expired()) || self.needs_redraw
+    }
+
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+        self.command_bar.resize(size);
+    }
+
+    fn draw(&mut self, origin: Position, screen: &mut Screen) {
+        if self.is_prompting() {
+            self.command_bar.draw(origin, screen);
+            return;
+        }
+        if self.current_message.is_expired() {
+            self.cleared_after_expiry = true;
+        }
+        let message = if self.current_message.is_expired() {
+            ""
+        } else {
+            &self.current_message.text
+        };
+        screen.print_row(origin.row, message);
+    }
+}
@@ -0,0 +1,215 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::style::{Style, StyledText};
+use crate::terminal::{Position, Size};
+
+// 屏幕上的一个格子
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Cell {
+    // 宽字符占两个格子, 后一个格子的symbol是空的
+    pub symbol: String,
+    pub style: Style,
+}
+
+impl Cell {
+    fn blank(style: Style) -> Self {
+        Self {
+            symbol: String::from(" "),
+            style,
+        }
+    }
+
+    // 宽字符的后半个格子, 画前半个的时候就已经画好了
+    pub fn is_continuation(&self) -> bool {
+        self.symbol.is_empty()
+    }
+
+    // 这个格子的内容在终端上占几列
+    pub fn width(&self) -> usize {
+        self.symbol.width().min(2)
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self::blank(Style::default())
+    }
+}
+
+// 两处变化之间最多隔几个格子时, 直接重画中间的格子
+const MAX_GAP: usize = 4;
+
+// 一帧画面, 各个组件先画到这里, 再和上一帧比较, 只把变化的格子输出到终端
+#[derive(Clone, Default)]
+pub struct Screen {
+    cells: Vec<Vec<Cell>>,
+}
+
+impl Screen {
+    pub fn new(size: Size) -> Self {
+        Self {
+            cells: vec![vec![Cell::default(); size.width]; size.height],
+        }
+    }
+
+    pub fn cell(&self, position: Position) -> Option<&Cell> {
+        self.cells.get(position.row)?.get(position.col)
+    }
+
+    // 清空这一行, 再从行首打印text
+    pub fn print_row(&mut self, row: usize, text: &str) {
+        self.print_styled_row(row, Style::default(), &[StyledText::new(text, Style::default())]);
+    }
+
+    // 打印一行由多段不同样式组成的文本, 文本之后的空白用fill的样式填满
+    pub fn print_styled_row(&mut self, row: usize, fill: Style, segments: &[StyledText]) {
+        let width = self.cells.get(row).map_or(0, Vec::len);
+        self.print_styled(Position { row, col: 0 }, width, fill, segments);
+    }
+
+    // 在origin开始的width个格子里打印, 只改动这些格子, 窗口左右排列时互不影响
+    // 放不下的部分丢掉, 末尾放不下的宽字符也不画
+    pub fn print_styled(&mut self, origin: Position, width: usize, fill: Style, segments: &[StyledText]) {
+        let Some(row) = self.cells.get_mut(origin.row) else {
+            return;
+        };
+        let start = origin.col.min(row.len());
+        let end = origin.col.saturating_add(width).min(row.len());
+        let cells = &mut row[start..end];
+        cells.fill(Cell::blank(fill));
+        let mut col = 0;
+        for segment in segments {
+            for grapheme in segment.text.graphemes(true) {
+                let width = grapheme.width().min(2);
+                if width == 0 {
+                    continue;
+                }
+                if col + width > cells.len() {
+                    return;
+                }
+                cells[col] = Cell {
+                    symbol: grapheme.to_string(),
+                    style: segment.style,
+                };
+                if width == 2 {
+                    cells[col + 1] = Cell {
+                        symbol: String::new(),
+                        style: segment.style,
+                    };
+                }
+                col += width;
+            }
+        }
+    }
+
+    // 和上一帧相比变化了的格子, 按照从上到下, 从左到右的顺序
+    // 宽字符的后半个格子不会单独出现, 画前半个的时候会一起画出来
+    // 同一行里两处变化之间只隔了几个格子时, 把中间的格子也重画一遍, 这比移动光标的转义序列还短
+    pub fn diff<'a>(&'a self, previous: &Self) -> Vec<(Position, &'a Cell)> {
+        let mut changes = Vec::new();
+        for (row, cells) in self.cells.iter().enumerate() {
+            // 这一行上一处变化之后的第一个格子
+            let mut last_end = None;
+            for (col, cell) in cells.iter().enumerate() {
+                let position = Position { row, col };
+                if cell.is_continuation() || previous.cell(position) == Some(cell) {
+                    continue;
+                }
+                if let Some(end) = last_end.filter(|&end| col - end <= MAX_GAP) {
+                    changes.extend(
+                        (end..col)
+                            .filter(|&col| !cells[col].is_continuation())
+                            .map(|col| (Position { row, col }, &cells[col])),
+                    );
+                }
+                changes.push((position, cell));
+                last_end = Some(col + cell.width());
+            }
+        }
+        changes
+    }
+
+    pub fn resize(&mut self, size: Size) {
+        self.cells.resize_with(size.height, Vec::new);
+        for row in &mut self.cells {
+            row.resize(size.width, Cell::default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Screen;
+    use crate::terminal::Size;
+
+    fn screen(width: usize, text: &str) -> Screen {
+        let mut screen = Screen::new(Size { height: 1, width });
+        screen.print_row(0, text);
+        screen
+    }
+
+    // 每个变化的格子写成(列, 内容)
+    fn changes(screen: &Screen, previous: &Screen) -> Vec<(usize, String)> {
+        screen
+            .diff(previous)
+            .into_iter()
+            .map(|(position, cell)| (position.col, cell.symbol.clone()))
+            .collect()
+    }
+
+    fn symbols(screen: &Screen) -> Vec<String> {
+        screen.cells[0].iter().map(|cell| cell.symbol.clone()).collect()
+    }
+
+    #[test]
+    fn unchanged_frame_has_no_changes() {
+        let previous = screen(8, "ab世c");
+        assert!(changes(&previous.clone(), &previous).is_empty());
+    }
+
+    #[test]
+    fn wide_grapheme_replaces_two_narrow_cells() {
+        let previous = screen(6, "ab");
+        let current = screen(6, "世");
+        assert_eq!(symbols(&current)[..2], ["世", ""]);
+        // 后半个格子跟着前半个一起输出
+        assert_eq!(changes(&current, &previous), [(0, String::from("世"))]);
+    }
+
+    #[test]
+    fn narrow_cells_replace_wide_grapheme() {
+        let previous = screen(6, "世");
+        let current = screen(6, "ab");
+        assert_eq!(
+            changes(&current, &previous),
+            [(0, String::from("a")), (1, String::from("b"))]
+        );
+    }
+
+    #[test]
+    fn wide_grapheme_straddling_last_column_is_dropped() {
+        let current = screen(5, "abcd世");
+        assert_eq!(symbols(&current), ["a", "b", "c", "d", " "]);
+        let previous = screen(5, "");
+        assert_eq!(changes(&current, &previous).len(), 4);
+    }
+
+    #[test]
+    fn small_gaps_are_filled() {
+        let previous = screen(12, "");
+        // 中间隔了MAX_GAP个没变的格子, 一起重画
+        let current = screen(12, "x    y");
+        let cols: Vec<usize> = changes(&current, &previous).into_iter().map(|(col, _)| col).collect();
+        assert_eq!(cols, [0, 1, 2, 3, 4, 5]);
+        // 再多隔一个就分开输出
+        let current = screen(12, "x     y");
+        let cols: Vec<usize> = changes(&current, &previous).into_iter().map(|(col, _)| col).collect();
+        assert_eq!(cols, [0, 6]);
+        // 补上的格子里也不会单独出现宽字符的后半个
+        let previous = screen(12, "a世 b");
+        let current = screen(12, "c世 d");
+        let cols: Vec<usize> = changes(&current, &previous).into_iter().map(|(col, _)| col).collect();
+        assert_eq!(cols, [0, 1, 3, 4]);
+    }
+}
@@ -1,6 +1,8 @@
-use std::io::Error;
 use crate::documentstatus::DocumentStatus;
-use crate::terminal::{Size, Terminal};
+use crate::screen::Screen;
+use crate::style::StyledText;
+use crate::terminal::{Position, Size};
+use crate::theme::Theme;
 use crate::uicomponent::UIComponent;
 
 #[derive(Default)]
@@ -8,6 +10,9 @@ pub struct StatusBar {
     current_status: DocumentStatus,
     needs_redraw: bool,
     size: Size,
+    theme: Theme,
+    // 有输入焦点的窗口的状态栏, 和其他窗口的用不同的样式
+    focused: bool,
 }
 
 impl StatusBar {
@@ -17,6 +22,18 @@ impl StatusBar {
             self.mark_redraw(true);
         }
     }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.mark_redraw(true);
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        if focused != self.focused {
+            self.focused = focused;
+            self.mark_redraw(true);
+        }
+    }
 }
 
 impl UIComponent for StatusBar {
@@ -32,27 +49,36 @@ impl UIComponent for StatusBar {
         self.size = size;
     }
 
-    // 画在origin_y这一行
-    fn draw(&mut self, origin_y: usize) -> Result<(), Error> {
+    // 画在origin这一行, 占满自己的宽度
+    fn draw(&mut self, origin: Position, screen: &mut Screen) {
         // Assemble the first part of the status bar
         let line_count = self.current_status.line_count_to_string();
         let modified_indicator = self.current_status.modified_indicator_to_string();
+        let read_only_indicator = self.current_status.read_only_indicator_to_string();
         let beginning = format!(
-            "{} - {line_count} {modified_indicator}",
+            "{}{read_only_indicator} - {line_count} {modified_indicator}",
             self.current_status.filename
         );
         // Assemble the whole status bar, with the position indicator at the back
-        let position_indicator = self.current_status.position_indicator_to_string();
+        let position_indicator = format!(
+            "{} | {}",
+            self.current_status.file_format_to_string(),
+            self.current_status.position_indicator_to_string()
+        );
         let remainder_len = self.size.width.saturating_sub(beginning.len());
         let status = format!("{beginning}{position_indicator:>remainder_len$}");
-        // Only print out the status if it fits. Otherwise write out an empty string to ensure the row is cleared.
+        // 放不下的时候只显示前半部分, 左右分割出来的窄窗口也能看到文件名
         let to_print = if status.len() <= self.size.width {
             status
         } else {
-            String::new()
+            beginning.chars().take(self.size.width).collect()
+        };
+        let width = self.size.width;
+        let style = if self.focused {
+            self.theme.status_bar
+        } else {
+            self.theme.inactive_status_bar
         };
-        let result = Terminal::print_inverted_row(origin_y, &to_print);
-        debug_assert!(result.is_ok(), "Failed to render status bar");
-        result
+        screen.print_styled(origin, width, style, &[StyledText::new(format!("{to_print:width$}"), style)]);
     }
 }
\ No newline at end of file
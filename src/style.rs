@@ -0,0 +1,265 @@
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+use crossterm::Command;
+use serde::Deserialize;
+
+// 终端支持的颜色数量
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ColorSupport {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorSupport {
+    // 根据 $COLORTERM 和 $TERM 判断终端支持多少种颜色
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        if matches!(colorterm.as_str(), "truecolor" | "24bit") {
+            return Self::TrueColor;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+}
+
+// 主题里用到的颜色, 在主题文件里可以写成:
+// "red", "bright-blue" 这样的16色名字, 0-255的256色下标, 或者 "#rrggbb"
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize)]
+#[serde(try_from = "ColorValue")]
+pub enum Color {
+    // 终端调色板里的16种颜色, 0-7是普通的, 8-15是亮色
+    Ansi(u8),
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Index(u8),
+    Name(String),
+}
+
+impl TryFrom<ColorValue> for Color {
+    type Error = String;
+
+    fn try_from(value: ColorValue) -> Result<Self, Self::Error> {
+        match value {
+            ColorValue::Index(index) => Ok(Self::Indexed(index)),
+            ColorValue::Name(name) => name.parse(),
+        }
+    }
+}
+
+const COLOR_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+// xterm默认的16色调色板, 降级时用来找最接近的颜色
+const ANSI_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (128, 0, 0),
+    (0, 128, 0),
+    (128, 128, 0),
+    (0, 0, 128),
+    (128, 0, 128),
+    (0, 128, 128),
+    (192, 192, 192),
+    (128, 128, 128),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (0, 0, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+// 256色中6x6x6色块每一级的亮度
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let name = value.trim().to_ascii_lowercase();
+        if let Some(hex) = name.strip_prefix('#') {
+            let channel = |index: usize| hex.get(index..index + 2).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(Self::Rgb(r, g, b)),
+                _ => Err(format!("invalid color: {value}")),
+            };
+        }
+        if matches!(name.as_str(), "grey" | "gray") {
+            return Ok(Self::Ansi(8));
+        }
+        let (bright, base) = match name.strip_prefix("bright-") {
+            Some(base) => (8, base),
+            None => (0, name.as_str()),
+        };
+        COLOR_NAMES
+            .iter()
+            .position(|&color| color == base)
+            .map(|index| Self::Ansi(index as u8 + bright))
+            .ok_or_else(|| format!("invalid color: {value}"))
+    }
+}
+
+impl Color {
+    // 转换成终端支持的颜色
+    pub fn downgrade(self, support: ColorSupport) -> Self {
+        match (self, support) {
+            (Self::Rgb(r, g, b), ColorSupport::Ansi256) => Self::Indexed(Self::nearest_indexed(r, g, b)),
+            (Self::Rgb(..) | Self::Indexed(_), ColorSupport::Ansi16) => {
+                let (r, g, b) = self.to_rgb();
+                Self::Ansi(Self::nearest(&ANSI_PALETTE, (r, g, b)))
+            }
+            _ => self,
+        }
+    }
+
+    fn to_rgb(self) -> (u8, u8, u8) {
+        match self {
+            Self::Rgb(r, g, b) => (r, g, b),
+            Self::Ansi(index) => ANSI_PALETTE[usize::from(index % 16)],
+            Self::Indexed(index) if index < 16 => ANSI_PALETTE[usize::from(index)],
+            // 16-231是6x6x6的色块
+            Self::Indexed(index) if index < 232 => {
+                let index = usize::from(index - 16);
+                (CUBE_LEVELS[index / 36], CUBE_LEVELS[index / 6 % 6], CUBE_LEVELS[index % 6])
+            }
+            // 232-255是从深到浅的24级灰色
+            Self::Indexed(index) => {
+                let level = 8 + (index - 232) * 10;
+                (level, level, level)
+            }
+        }
+    }
+
+    // 在色块和灰阶里分别找最接近的, 再取两者中更接近的
+    fn nearest_indexed(r: u8, g: u8, b: u8) -> u8 {
+        let level = |value: u8| {
+            (0..CUBE_LEVELS.len())
+                .min_by_key(|&index| CUBE_LEVELS[index].abs_diff(value))
+                .unwrap_or(0) as u8
+        };
+        let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+        let average = (r as usize + g as usize + b as usize) / 3;
+        let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+        let distance = |index: u8| Self::distance(Self::Indexed(index).to_rgb(), (r, g, b));
+        if distance(gray) < distance(cube) {
+            gray
+        } else {
+            cube
+        }
+    }
+
+    fn nearest(palette: &[(u8, u8, u8)], color: (u8, u8, u8)) -> u8 {
+        (0..palette.len())
+            .min_by_key(|&index| Self::distance(palette[index], color))
+            .unwrap_or(0) as u8
+    }
+
+    fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+        let diff = |x: u8, y: u8| u32::from(x.abs_diff(y)).pow(2);
+        diff(a.0, b.0) + diff(a.1, b.1) + diff(a.2, b.2)
+    }
+
+    // SGR参数, background为true时是背景色
+    fn write_sgr(self, f: &mut impl fmt::Write, background: bool) -> fmt::Result {
+        let base = if background { 40 } else { 30 };
+        match self {
+            Self::Ansi(index) if index < 8 => write!(f, ";{}", base + index),
+            Self::Ansi(index) => write!(f, ";{}", base + 60 + index % 16 - 8),
+            Self::Indexed(index) => write!(f, ";{};5;{index}", base + 8),
+            Self::Rgb(r, g, b) => write!(f, ";{};2;{r};{g};{b}", base + 8),
+        }
+    }
+}
+
+// 文本的样式, 没有设置的颜色使用终端的默认颜色
+#[derive(Default, Copy, Clone, Eq, PartialEq, Debug, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+impl Style {
+    pub const fn fg(color: Color) -> Self {
+        Self {
+            fg: Some(color),
+            bg: None,
+            bold: false,
+            italic: false,
+            underline: false,
+            reverse: false,
+        }
+    }
+
+    // 把other叠加在上面, other没有设置的颜色保留原来的
+    pub fn patch(self, other: Self) -> Self {
+        Self {
+            fg: other.fg.or(self.fg),
+            bg: other.bg.or(self.bg),
+            bold: self.bold || other.bold,
+            italic: self.italic || other.italic,
+            underline: self.underline || other.underline,
+            reverse: self.reverse || other.reverse,
+        }
+    }
+
+    pub fn downgrade(self, support: ColorSupport) -> Self {
+        Self {
+            fg: self.fg.map(|color| color.downgrade(support)),
+            bg: self.bg.map(|color| color.downgrade(support)),
+            ..self
+        }
+    }
+}
+
+// 一段使用同一个样式的文本
+pub struct StyledText {
+    pub text: String,
+    pub style: Style,
+}
+
+impl StyledText {
+    pub fn new(text: impl Into<String>, style: Style) -> Self {
+        Self {
+            text: text.into(),
+            style,
+        }
+    }
+}
+
+// 先重置之前的样式, 再设置新的样式
+pub struct SetStyle(pub Style);
+
+impl Command for SetStyle {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        let style = self.0;
+        f.write_str("\x1b[0")?;
+        for (enabled, code) in [(style.bold, 1), (style.italic, 3), (style.underline, 4), (style.reverse, 7)] {
+            if enabled {
+                write!(f, ";{code}")?;
+            }
+        }
+        if let Some(fg) = style.fg {
+            fg.write_sgr(f, false)?;
+        }
+        if let Some(bg) = style.bg {
+            bg.write_sgr(f, true)?;
+        }
+        f.write_str("m")
+    }
+}
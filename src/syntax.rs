@@ -0,0 +1,709 @@
+use std::ops::Range;
+use std::path::Path;
+
+use crate::annotation::AnnotationType;
+
+// 支持语法高亮的语言
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Language {
+    Rust,
+    Markdown,
+    Toml,
+    Json,
+    Shell,
+}
+
+// 一行结束时还没有结束的结构, 下一行要从这个状态继续
+#[derive(Default, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LineState {
+    #[default]
+    Normal,
+    // 块注释, Rust的块注释可以嵌套, 记录嵌套的层数
+    BlockComment(usize),
+    // 跨行的字符串, 记录引号
+    String(char),
+    // Rust的原始字符串 r#"..."#, 记录#的个数
+    RawString(usize),
+    // TOML的多行字符串 """...""" 或 '''...''', 记录引号
+    MultilineString(char),
+    // Markdown的代码块, 记录围栏的字符和长度
+    CodeBlock(char, usize),
+}
+
+// 高亮的结果, range是字节下标
+pub struct Span {
+    pub range: Range<usize>,
+    pub annotation_type: AnnotationType,
+}
+
+impl Language {
+    // 根据扩展名(或者一些常见的文件名)判断语言
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if matches!(name, ".bashrc" | ".bash_profile" | ".zshrc" | ".profile") {
+            return Some(Self::Shell);
+        }
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "rs" => Some(Self::Rust),
+            "md" | "markdown" => Some(Self::Markdown),
+            "toml" => Some(Self::Toml),
+            "json" => Some(Self::Json),
+            "sh" | "bash" | "zsh" => Some(Self::Shell),
+            _ => None,
+        }
+    }
+
+    // 高亮一行, state是上一行结束时的状态, 返回高亮的结果和这一行结束时的状态
+    pub fn highlight(self, text: &str, state: LineState) -> (Vec<Span>, LineState) {
+        let mut scanner = Scanner::new(text);
+        let state = match self {
+            Self::Rust => rust(&mut scanner, state),
+            Self::Markdown => markdown(&mut scanner, state),
+            Self::Toml => toml(&mut scanner, state),
+            Self::Json => json(&mut scanner),
+            Self::Shell => shell(&mut scanner, state),
+        };
+        (scanner.spans, state)
+    }
+}
+
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Rust => "Rust",
+            Self::Markdown => "Markdown",
+            Self::Toml => "TOML",
+            Self::Json => "JSON",
+            Self::Shell => "Shell",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// 逐个字符扫描一行文本, 记录高亮的范围
+struct Scanner<'a> {
+    text: &'a str,
+    // 当前的字节下标
+    pos: usize,
+    spans: Vec<Span>,
+}
+
+impl<'a> Scanner<'a> {
+    const fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            pos: 0,
+            spans: Vec::new(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn prev(&self) -> Option<char> {
+        self.text[..self.pos].chars().next_back()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        if self.rest().starts_with(prefix) {
+            self.pos += prefix.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn skip_to_end(&mut self) {
+        self.pos = self.text.len();
+    }
+
+    // 跳过空白之后是不是ch
+    fn followed_by(&self, ch: char) -> bool {
+        self.rest().trim_start().starts_with(ch)
+    }
+
+    // 扫描到结束的引号, 找到了返回true, 到行尾还没有找到返回false
+    fn quoted(&mut self, quote: char, escapes: bool) -> bool {
+        while let Some(ch) = self.bump() {
+            if escapes && ch == '\\' {
+                self.bump();
+            } else if ch == quote {
+                return true;
+            }
+        }
+        false
+    }
+
+    // 把从start到当前位置标记成annotation_type
+    fn mark(&mut self, start: usize, annotation_type: AnnotationType) {
+        if self.pos > start {
+            self.spans.push(Span {
+                range: start..self.pos,
+                annotation_type,
+            });
+        }
+    }
+}
+
+fn is_ident_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self",
+    "static", "struct", "super", "trait", "type", "unsafe", "use", "where", "while", "yield",
+];
+
+const RUST_TYPES: &[&str] = &[
+    "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+    "f32", "f64",
+];
+
+fn rust(s: &mut Scanner, state: LineState) -> LineState {
+    // 先把上一行没有结束的部分扫完
+    match state {
+        LineState::BlockComment(depth) => {
+            if let Some(state) = rust_block_comment(s, 0, depth) {
+                return state;
+            }
+        }
+        LineState::String(_) => {
+            let closed = s.quoted('"', true);
+            s.mark(0, AnnotationType::String);
+            if !closed {
+                return state;
+            }
+        }
+        LineState::RawString(hashes) => {
+            let closed = rust_raw_string_end(s, hashes);
+            s.mark(0, AnnotationType::String);
+            if !closed {
+                return state;
+            }
+        }
+        _ => {}
+    }
+    while let Some(ch) = s.peek() {
+        let start = s.pos;
+        if s.eat("//") {
+            s.skip_to_end();
+            s.mark(start, AnnotationType::Comment);
+        } else if s.eat("/*") {
+            if let Some(state) = rust_block_comment(s, start, 1) {
+                return state;
+            }
+        } else if let Some(hashes) = rust_raw_string_start(s) {
+            let closed = rust_raw_string_end(s, hashes);
+            s.mark(start, AnnotationType::String);
+            if !closed {
+                return LineState::RawString(hashes);
+            }
+        } else if ch == '"' || (ch == 'b' && s.peek_nth(1) == Some('"')) {
+            s.eat("b");
+            s.bump();
+            let closed = s.quoted('"', true);
+            s.mark(start, AnnotationType::String);
+            if !closed {
+                return LineState::String('"');
+            }
+        } else if ch == '\'' {
+            rust_quote(s);
+        } else if ch.is_ascii_digit() {
+            number(s);
+        } else if is_ident_char(ch) {
+            let word = s.eat_while(is_ident_char);
+            let annotation_type = if RUST_KEYWORDS.contains(&word) {
+                Some(AnnotationType::Keyword)
+            } else if matches!(word, "true" | "false") {
+                Some(AnnotationType::Constant)
+            } else if RUST_TYPES.contains(&word) || word.starts_with(char::is_uppercase) {
+                Some(AnnotationType::Type)
+            } else {
+                None
+            };
+            if let Some(annotation_type) = annotation_type {
+                s.mark(start, annotation_type);
+            }
+        } else {
+            s.bump();
+        }
+    }
+    LineState::Normal
+}
+
+// 扫描嵌套的块注释, 在这一行结束时返回None, 否则返回下一行开始时的状态
+fn rust_block_comment(s: &mut Scanner, start: usize, mut depth: usize) -> Option<LineState> {
+    while depth > 0 {
+        if s.eat("/*") {
+            depth += 1;
+        } else if s.eat("*/") {
+            depth -= 1;
+        } else if s.bump().is_none() {
+            s.mark(start, AnnotationType::Comment);
+            return Some(LineState::BlockComment(depth));
+        }
+    }
+    s.mark(start, AnnotationType::Comment);
+    None
+}
+
+// r"..." r#"..."# br"...", 是原始字符串的开头时返回#的个数
+fn rust_raw_string_start(s: &mut Scanner) -> Option<usize> {
+    let rest = s.rest();
+    let after_prefix = rest.strip_prefix("br").or_else(|| rest.strip_prefix('r'))?;
+    let hashes = after_prefix.len() - after_prefix.trim_start_matches('#').len();
+    if !after_prefix[hashes..].starts_with('"') {
+        return None;
+    }
+    s.pos += rest.len() - after_prefix.len() + hashes + 1;
+    Some(hashes)
+}
+
+fn rust_raw_string_end(s: &mut Scanner, hashes: usize) -> bool {
+    let closing = format!("\"{}", "#".repeat(hashes));
+    if let Some(index) = s.rest().find(&closing) {
+        s.pos += index + closing.len();
+        true
+    } else {
+        s.skip_to_end();
+        false
+    }
+}
+
+// 'a' '\n' 是字符, 'a 是生命周期
+fn rust_quote(s: &mut Scanner) {
+    let start = s.pos;
+    s.bump();
+    if s.peek() == Some('\\') {
+        s.quoted('\'', true);
+        s.mark(start, AnnotationType::String);
+    } else if s.peek_nth(1) == Some('\'') {
+        s.bump();
+        s.bump();
+        s.mark(start, AnnotationType::String);
+    } else {
+        s.eat_while(is_ident_char);
+        s.mark(start, AnnotationType::Type);
+    }
+}
+
+// 数字, 包括 0x1F 1_000 1.5f32 这样的写法
+fn number(s: &mut Scanner) {
+    let start = s.pos;
+    s.bump();
+    loop {
+        match s.peek() {
+            Some(ch) if is_ident_char(ch) => {
+                s.bump();
+            }
+            // 1..10 中的点不算小数点
+            Some('.') if s.peek_nth(1).is_some_and(|ch| ch.is_ascii_digit()) => {
+                s.bump();
+            }
+            _ => break,
+        }
+    }
+    s.mark(start, AnnotationType::Number);
+}
+
+fn markdown(s: &mut Scanner, state: LineState) -> LineState {
+    let trimmed = s.text.trim_start();
+    let indent = s.text.len() - trimmed.len();
+    // 代码块里的内容原样显示, 直到遇到同样的围栏
+    if let LineState::CodeBlock(fence, count) = state {
+        s.skip_to_end();
+        s.mark(0, AnnotationType::String);
+        let closing = trimmed.chars().take_while(|&ch| ch == fence).count();
+        let is_closed = closing >= count && trimmed[closing * fence.len_utf8()..].trim().is_empty();
+        return if is_closed { LineState::Normal } else { state };
+    }
+    if indent <= 3 {
+        for fence in ['`', '~'] {
+            let count = trimmed.chars().take_while(|&ch| ch == fence).count();
+            if count >= 3 {
+                s.skip_to_end();
+                s.mark(0, AnnotationType::String);
+                return LineState::CodeBlock(fence, count);
+            }
+        }
+        let hashes = trimmed.chars().take_while(|&ch| ch == '#').count();
+        let is_heading = (1..=6).contains(&hashes) && trimmed[hashes..].chars().next().is_none_or(char::is_whitespace);
+        if is_heading || trimmed.starts_with('>') {
+            s.skip_to_end();
+            let annotation_type = if is_heading {
+                AnnotationType::Heading
+            } else {
+                AnnotationType::Comment
+            };
+            s.mark(0, annotation_type);
+            return LineState::Normal;
+        }
+    }
+    // 列表的标记: - * + 1. 1)
+    s.pos = indent;
+    let digits = s.eat_while(|ch| ch.is_ascii_digit());
+    let is_marker = if digits.is_empty() {
+        s.eat("- ") || s.eat("* ") || s.eat("+ ")
+    } else {
+        s.eat(". ") || s.eat(") ")
+    };
+    if is_marker {
+        s.mark(indent, AnnotationType::Keyword);
+    } else {
+        s.pos = indent;
+    }
+    while let Some(ch) = s.peek() {
+        let start = s.pos;
+        match ch {
+            '\\' => {
+                s.bump();
+                s.bump();
+            }
+            '`' => {
+                let ticks = s.eat_while(|ch| ch == '`');
+                if let Some(index) = s.rest().find(ticks) {
+                    s.pos += index + ticks.len();
+                    s.mark(start, AnnotationType::String);
+                }
+            }
+            // 单词中间的下划线不算强调
+            '_' if s.prev().is_some_and(char::is_alphanumeric) => {
+                s.eat_while(|ch| ch == '_');
+            }
+            '*' | '_' => {
+                let run = s.eat_while(|other| other == ch);
+                let delimiter = &run[..run.len().min(2)];
+                let content_start = s.pos;
+                let is_emphasis = !s.rest().starts_with(char::is_whitespace)
+                    && s.rest().find(delimiter).is_some_and(|index| index > 0);
+                if is_emphasis {
+                    if let Some(index) = s.rest().find(delimiter) {
+                        s.pos = content_start + index + delimiter.len();
+                        s.mark(start, AnnotationType::Emphasis);
+                    }
+                }
+            }
+            // 链接 [text](url), 标出url
+            '[' => {
+                s.bump();
+                let rest = s.rest();
+                let url = rest.find("](").and_then(|open| {
+                    let close = rest[open..].find(')')?;
+                    Some((open + 2, open + close))
+                });
+                if let Some((url_start, url_end)) = url {
+                    let url_start = s.pos + url_start;
+                    s.pos += url_end;
+                    s.mark(url_start, AnnotationType::Constant);
+                    s.bump();
+                }
+            }
+            _ => {
+                s.bump();
+            }
+        }
+    }
+    LineState::Normal
+}
+
+fn toml(s: &mut Scanner, state: LineState) -> LineState {
+    if let LineState::MultilineString(quote) = state {
+        let closed = toml_multiline_string_end(s, quote);
+        s.mark(0, AnnotationType::String);
+        if !closed {
+            return state;
+        }
+    } else {
+        toml_table_header(s);
+    }
+    while let Some(ch) = s.peek() {
+        let start = s.pos;
+        match ch {
+            '#' => {
+                s.skip_to_end();
+                s.mark(start, AnnotationType::Comment);
+            }
+            '"' | '\'' => {
+                let triple = ch.to_string().repeat(3);
+                if s.eat(&triple) {
+                    let closed = toml_multiline_string_end(s, ch);
+                    s.mark(start, AnnotationType::String);
+                    if !closed {
+                        return LineState::MultilineString(ch);
+                    }
+                } else {
+                    s.bump();
+                    s.quoted(ch, ch == '"');
+                    // 引号括起来的键
+                    let annotation_type = if s.followed_by('=') || s.followed_by('.') {
+                        AnnotationType::Key
+                    } else {
+                        AnnotationType::String
+                    };
+                    s.mark(start, annotation_type);
+                }
+            }
+            _ if is_ident_char(ch) || ch == '-' || ch == '+' => {
+                let word = s.eat_while(|ch| is_ident_char(ch) || matches!(ch, '-' | '+' | ':' | '.'));
+                let is_key = s.followed_by('=');
+                // 键也可以是 a.b.c 的形式, 整个标成键
+                let annotation_type = if is_key {
+                    AnnotationType::Key
+                } else if matches!(word, "true" | "false" | "inf" | "nan" | "+inf" | "-inf") {
+                    AnnotationType::Constant
+                } else if word.trim_start_matches(['+', '-']).starts_with(|ch: char| ch.is_ascii_digit()) {
+                    // 数字和日期
+                    AnnotationType::Number
+                } else {
+                    continue;
+                };
+                s.mark(start, annotation_type);
+            }
+            _ => {
+                s.bump();
+            }
+        }
+    }
+    LineState::Normal
+}
+
+// [table] 或者 [[array]], 整个标成标题
+fn toml_table_header(s: &mut Scanner) {
+    let trimmed = s.text.trim_start();
+    let start = s.text.len() - trimmed.len();
+    let (open, close) = if trimmed.starts_with("[[") {
+        ("[[", "]]")
+    } else if trimmed.starts_with('[') {
+        ("[", "]")
+    } else {
+        return;
+    };
+    let Some(end) = trimmed.find(close) else {
+        return;
+    };
+    let name = &trimmed[open.len()..end];
+    let after = trimmed[end + close.len()..].trim_start();
+    // 值里面的数组不是表头
+    if name.trim().is_empty() || name.contains('=') || !(after.is_empty() || after.starts_with('#')) {
+        return;
+    }
+    s.pos = start + end + close.len();
+    s.mark(start, AnnotationType::Heading);
+}
+
+fn toml_multiline_string_end(s: &mut Scanner, quote: char) -> bool {
+    let closing = quote.to_string().repeat(3);
+    while s.peek().is_some() {
+        if quote == '"' && s.eat("\\") {
+            s.bump();
+        } else if s.eat(&closing) {
+            return true;
+        } else {
+            s.bump();
+        }
+    }
+    false
+}
+
+fn json(s: &mut Scanner) -> LineState {
+    while let Some(ch) = s.peek() {
+        let start = s.pos;
+        match ch {
+            '"' => {
+                s.bump();
+                s.quoted('"', true);
+                let annotation_type = if s.followed_by(':') {
+                    AnnotationType::Key
+                } else {
+                    AnnotationType::String
+                };
+                s.mark(start, annotation_type);
+            }
+            '-' | '0'..='9' => {
+                s.eat_while(|ch| ch.is_ascii_digit() || matches!(ch, '+' | '-' | '.' | 'e' | 'E'));
+                s.mark(start, AnnotationType::Number);
+            }
+            _ if ch.is_alphabetic() => {
+                let word = s.eat_while(char::is_alphanumeric);
+                if matches!(word, "true" | "false" | "null") {
+                    s.mark(start, AnnotationType::Constant);
+                }
+            }
+            _ => {
+                s.bump();
+            }
+        }
+    }
+    LineState::Normal
+}
+
+const SHELL_KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac", "in", "function",
+    "return", "local", "export", "select", "time", "break", "continue", "exit", "readonly", "declare", "unset",
+    "shift", "source",
+];
+
+fn shell(s: &mut Scanner, state: LineState) -> LineState {
+    if let LineState::String(quote) = state {
+        let closed = s.quoted(quote, quote == '"');
+        s.mark(0, AnnotationType::String);
+        if !closed {
+            return state;
+        }
+    }
+    while let Some(ch) = s.peek() {
+        let start = s.pos;
+        match ch {
+            // 单词中间的#不是注释
+            '#' if s.prev().is_none_or(char::is_whitespace) => {
+                s.skip_to_end();
+                s.mark(start, AnnotationType::Comment);
+            }
+            '"' | '\'' => {
+                s.bump();
+                let closed = s.quoted(ch, ch == '"');
+                s.mark(start, AnnotationType::String);
+                if !closed {
+                    return LineState::String(ch);
+                }
+            }
+            '$' => {
+                s.bump();
+                match s.peek() {
+                    Some('{') => {
+                        s.quoted('}', false);
+                    }
+                    Some('(') => {
+                        s.bump();
+                    }
+                    Some(ch) if is_ident_char(ch) => {
+                        s.eat_while(is_ident_char);
+                    }
+                    Some('?' | '#' | '@' | '*' | '!' | '$' | '-') => {
+                        s.bump();
+                    }
+                    _ => {}
+                }
+                s.mark(start, AnnotationType::Variable);
+            }
+            _ if ch.is_whitespace() || ";&|()<>`=".contains(ch) => {
+                s.bump();
+            }
+            _ => {
+                let word = s.eat_while(|ch| !ch.is_whitespace() && !";&|()<>\"'$`=#".contains(ch));
+                if word.is_empty() {
+                    s.bump();
+                } else if SHELL_KEYWORDS.contains(&word) {
+                    s.mark(start, AnnotationType::Keyword);
+                } else if word.chars().all(|ch| ch.is_ascii_digit()) {
+                    s.mark(start, AnnotationType::Number);
+                }
+            }
+        }
+    }
+    LineState::Normal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Language, LineState};
+    use crate::annotation::AnnotationType;
+
+    // 逐行高亮, 每行返回高亮出来的(文本, 类型)和这一行结束时的状态
+    fn highlight(language: Language, lines: &[&str]) -> Vec<(Vec<(String, AnnotationType)>, LineState)> {
+        let mut state = LineState::default();
+        lines
+            .iter()
+            .map(|line| {
+                let (spans, state_out) = language.highlight(line, state);
+                state = state_out;
+                let spans = spans
+                    .into_iter()
+                    .map(|span| (line[span.range].to_string(), span.annotation_type))
+                    .collect();
+                (spans, state_out)
+            })
+            .collect()
+    }
+
+    fn span(text: &str, annotation_type: AnnotationType) -> (String, AnnotationType) {
+        (text.to_string(), annotation_type)
+    }
+
+    #[test]
+    fn rust_nested_block_comment_spans_lines() {
+        let lines = highlight(Language::Rust, &["let a; /* outer /* inner", "still */ comment", "*/ let b;"]);
+        assert_eq!(lines[0].0.last(), Some(&span("/* outer /* inner", AnnotationType::Comment)));
+        assert_eq!(lines[0].1, LineState::BlockComment(2));
+        // 内层的注释结束了, 外层的还没有
+        assert_eq!(lines[1].0, [span("still */ comment", AnnotationType::Comment)]);
+        assert_eq!(lines[1].1, LineState::BlockComment(1));
+        assert_eq!(lines[2].0, [span("*/", AnnotationType::Comment), span("let", AnnotationType::Keyword)]);
+        assert_eq!(lines[2].1, LineState::Normal);
+    }
+
+    #[test]
+    fn rust_raw_string_spans_lines() {
+        let lines = highlight(Language::Rust, &[r##"let s = r#"has "quotes""##, r##"end"# ; true"##]);
+        assert_eq!(lines[0].0.last(), Some(&span(r##"r#"has "quotes""##, AnnotationType::String)));
+        // 只有"后面跟着同样多的#才算结束
+        assert_eq!(lines[0].1, LineState::RawString(1));
+        assert_eq!(lines[1].0, [span(r##"end"#"##, AnnotationType::String), span("true", AnnotationType::Constant)]);
+        assert_eq!(lines[1].1, LineState::Normal);
+    }
+
+    #[test]
+    fn toml_multiline_string() {
+        let lines = highlight(Language::Toml, &[r#"text = """first"#, "# not a comment", r#"last""" # comment"#]);
+        assert_eq!(lines[0].0, [span("text", AnnotationType::Key), span(r#""""first"#, AnnotationType::String)]);
+        assert_eq!(lines[0].1, LineState::MultilineString('"'));
+        assert_eq!(lines[1].0, [span("# not a comment", AnnotationType::String)]);
+        assert_eq!(lines[2].0, [span(r#"last""""#, AnnotationType::String), span("# comment", AnnotationType::Comment)]);
+        assert_eq!(lines[2].1, LineState::Normal);
+    }
+
+    #[test]
+    fn markdown_fence() {
+        let lines = highlight(Language::Markdown, &["````rust", "# not a heading", "```", "````", "# heading"]);
+        assert_eq!(lines[0].1, LineState::CodeBlock('`', 4));
+        assert_eq!(lines[1].0, [span("# not a heading", AnnotationType::String)]);
+        // 比开头短的围栏不能结束代码块
+        assert_eq!(lines[2].1, LineState::CodeBlock('`', 4));
+        assert_eq!(lines[3].1, LineState::Normal);
+        assert_eq!(lines[4].0, [span("# heading", AnnotationType::Heading)]);
+    }
+
+    #[test]
+    fn shell_string_spans_lines() {
+        let lines = highlight(Language::Shell, &["echo \"one", "# two $HOME", "three\" # done"]);
+        assert_eq!(lines[0].0, [span("\"one", AnnotationType::String)]);
+        assert_eq!(lines[0].1, LineState::String('"'));
+        assert_eq!(lines[1].0, [span("# two $HOME", AnnotationType::String)]);
+        assert_eq!(lines[2].0, [span("three\"", AnnotationType::String), span("# done", AnnotationType::Comment)]);
+        assert_eq!(lines[2].1, LineState::Normal);
+    }
+}
@@ -1,10 +1,14 @@
-use std::fmt::Display;
 use std::io::{Error, stdout, Write};
 
 use crossterm::cursor::{Hide, MoveTo, Show};
 use crossterm::{Command, queue};
-use crossterm::style::{Attribute, Print};
-use crossterm::terminal::{Clear, ClearType, DisableLineWrap, enable_raw_mode, EnableLineWrap, EnterAlternateScreen, LeaveAlternateScreen, SetTitle, size};
+use crossterm::event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture};
+use crossterm::style::Print;
+use crossterm::terminal::{Clear, ClearType, DisableLineWrap, disable_raw_mode, enable_raw_mode, EnableLineWrap, EnterAlternateScreen, LeaveAlternateScreen, SetTitle, size};
+
+use crate::clipboard::SetClipboard;
+use crate::screen::Cell;
+use crate::style::{SetStyle, Style};
 
 #[derive(Default, Copy, Clone, Eq, PartialEq)]
 pub struct Size {
@@ -14,20 +18,13 @@ pub struct Size {
 
 // 屏幕上的行和列
 // 描述渲染内容在窗口内位置的结构
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Default, Eq, PartialEq)]
 pub struct Position {
     pub col: usize,
     pub row: usize,
 }
 
 impl Position {
-    pub fn new(col: usize, row: usize) -> Self {
-        Self {
-            col,
-            row,
-        }
-    }
-
     pub const fn saturating_sub(self, other: Self) -> Self {
         Self {
             row: self.row.saturating_sub(other.row),
@@ -36,62 +33,56 @@ impl Position {
     }
 }
 
-pub struct Terminal {}
+// 编辑器对终端的所有操作, 画面先排队, 调用execute之后才真正输出
+// 除了真实的终端, 还可以换成内存里的实现, 这样没有TTY也能测试
+pub trait Backend {
+    // 进入raw mode和备用屏幕, 开启鼠标和括号粘贴
+    fn initialize(&mut self) -> Result<(), Error>;
+    // 恢复initialize之前的状态
+    fn terminate(&mut self) -> Result<(), Error>;
+    fn size(&self) -> Result<Size, Error>;
+    fn clear_screen(&mut self) -> Result<(), Error>;
+    fn move_caret_to(&mut self, position: Position) -> Result<(), Error>;
+    fn hide_caret(&mut self) -> Result<(), Error>;
+    fn show_caret(&mut self) -> Result<(), Error>;
+    // 把变化了的格子画到屏幕上, changes按照从上到下, 从左到右排好了序
+    fn draw(&mut self, changes: &[(Position, &Cell)]) -> Result<(), Error>;
+    // 在光标处打印, 退出之后打印告别信息用
+    fn print(&mut self, text: &str) -> Result<(), Error>;
+    fn set_title(&mut self, title: &str) -> Result<(), Error>;
+    // 通过OSC 52让终端把text放进剪贴板, 不支持的终端会忽略它
+    fn set_clipboard(&mut self, text: &str) -> Result<(), Error>;
+    // 把排队的内容输出到屏幕上
+    fn execute(&mut self) -> Result<(), Error>;
+}
 
-impl Terminal {
-    pub fn initialize() -> Result<(), Error> {
-        enable_raw_mode()?;
-        Self::enter_alternate_screen()?;
-        Self::disable_line_wrap()?;
-        Self::clear_screen()?;
-        Self::execute()
-    }
+// 通过crossterm操作stdout所在的终端
+pub struct Terminal;
 
-    pub fn terminate() -> Result<(), Error> {
-        Self::leave_alternate_screen()?;
-        Self::enable_line_wrap()?;
-        Self::show_caret()?;
-        Self::execute()?;
+impl Backend for Terminal {
+    fn initialize(&mut self) -> Result<(), Error> {
         enable_raw_mode()?;
-        Ok(())
-    }
-
-    pub fn clear_screen() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::All))?;
-        Ok(())
-    }
-
-    pub fn clear_line() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::CurrentLine))?;
-        Ok(())
-    }
-
-    pub fn move_caret_to(pos: Position) -> Result<(), Error> {
-        Self::queue_command(MoveTo(pos.col as u16, pos.row as u16))?;
-        Ok(())
-    }
-
-    pub fn hide_caret() -> Result<(), Error> {
-        Self::queue_command(Hide)?;
-        Ok(())
-    }
-
-    pub fn show_caret() -> Result<(), Error> {
-        Self::queue_command(Show)?;
-        Ok(())
-    }
-
-    pub fn enter_alternate_screen() -> Result<(), Error> {
         Self::queue_command(EnterAlternateScreen)?;
-        Ok(())
+        // 开启后粘贴的内容会作为一个整体事件到达, 而不是一个个按键
+        Self::queue_command(EnableBracketedPaste)?;
+        Self::queue_command(EnableMouseCapture)?;
+        Self::queue_command(DisableLineWrap)?;
+        self.clear_screen()?;
+        self.execute()
     }
 
-    pub fn leave_alternate_screen() -> Result<(), Error> {
+    fn terminate(&mut self) -> Result<(), Error> {
+        Self::queue_command(DisableBracketedPaste)?;
+        Self::queue_command(DisableMouseCapture)?;
         Self::queue_command(LeaveAlternateScreen)?;
+        Self::queue_command(EnableLineWrap)?;
+        self.show_caret()?;
+        self.execute()?;
+        disable_raw_mode()?;
         Ok(())
     }
 
-    pub fn size() -> Result<Size, Error> {
+    fn size(&self) -> Result<Size, Error> {
         let (width, height) = size()?;
         Ok(Size {
             height: height as usize,
@@ -99,52 +90,67 @@ impl Terminal {
         })
     }
 
-    pub fn print_row(row: usize, line_text: &str) -> Result<(), Error> {
-        Self::move_caret_to(Position { row, col: 0 })?;
-        Self::clear_line()?;
-        Self::print(line_text)?;
-        Ok(())
+    fn clear_screen(&mut self) -> Result<(), Error> {
+        Self::queue_command(Clear(ClearType::All))
     }
 
-    pub fn print<T: Display>(o: T) -> Result<(), Error> {
-        Self::queue_command(Print(o))
+    fn move_caret_to(&mut self, position: Position) -> Result<(), Error> {
+        Self::queue_command(MoveTo(position.col as u16, position.row as u16))
     }
 
-    pub fn execute() -> Result<(), Error> {
-        stdout().flush()?;
-        Ok(())
+    fn hide_caret(&mut self) -> Result<(), Error> {
+        Self::queue_command(Hide)
     }
 
-    pub fn print_inverted_row(row: usize, line_text: &str) -> Result<(), Error> {
-        let width = Self::size()?.width;
-        Self::print_row(
-            row,
-            &format!(
-                "{}{:width$.width$}{}",
-                Attribute::Reverse,
-                line_text,
-                Attribute::Reset
-            ),
-        )
+    fn show_caret(&mut self) -> Result<(), Error> {
+        Self::queue_command(Show)
     }
 
-    pub fn disable_line_wrap() -> Result<(), Error> {
-        Self::queue_command(DisableLineWrap)?;
+    // 连续的格子只在开头移动一次光标, 样式变化时才重新设置样式
+    fn draw(&mut self, changes: &[(Position, &Cell)]) -> Result<(), Error> {
+        let mut caret = None;
+        let mut style = None;
+        for &(position, cell) in changes {
+            if caret != Some(position) {
+                self.move_caret_to(position)?;
+            }
+            if style != Some(cell.style) {
+                Self::queue_command(SetStyle(cell.style))?;
+                style = Some(cell.style);
+            }
+            self.print(&cell.symbol)?;
+            caret = Some(Position {
+                row: position.row,
+                col: position.col.saturating_add(cell.width()),
+            });
+        }
+        if style.is_some() {
+            Self::queue_command(SetStyle(Style::default()))?;
+        }
         Ok(())
     }
 
-    pub fn enable_line_wrap() -> Result<(), Error> {
-        Self::queue_command(EnableLineWrap)?;
-        Ok(())
+    fn print(&mut self, text: &str) -> Result<(), Error> {
+        Self::queue_command(Print(text))
     }
 
-    pub fn set_title(title: &str) -> Result<(), Error> {
-        Self::queue_command(SetTitle(title))?;
+    fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        Self::queue_command(SetTitle(title))
+    }
+
+    fn set_clipboard(&mut self, text: &str) -> Result<(), Error> {
+        Self::queue_command(SetClipboard(text))
+    }
+
+    fn execute(&mut self) -> Result<(), Error> {
+        stdout().flush()?;
         Ok(())
     }
+}
 
+impl Terminal {
     fn queue_command<T: Command>(command: T) -> Result<(), Error> {
         queue!(stdout(), command)?;
         Ok(())
     }
-}
\ No newline at end of file
+}
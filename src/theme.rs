@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::annotation::AnnotationType;
+use crate::config::Config;
+use crate::style::{Color, ColorSupport, Style};
+
+// 界面各部分的样式, 从 ~/.config/rs-editor/themes/<name>.toml 读取
+// 每一项都是一个表, 比如:
+// [keyword]
+// fg = "#c678dd"
+// bold = true
+// 没有写的项使用默认主题的样式
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    // 正文, 也是其他样式的底色
+    pub text: Style,
+    pub welcome: Style,
+    // 文件末尾之后的 ~
+    pub filler: Style,
+    // 有输入焦点的窗口的状态栏
+    pub status_bar: Style,
+    pub inactive_status_bar: Style,
+    // 左右排列的窗口之间的分隔线
+    pub separator: Style,
+    pub line_number: Style,
+    pub current_line_number: Style,
+    pub selection: Style,
+    pub search_match: Style,
+    pub selected_match: Style,
+    pub comment: Style,
+    pub keyword: Style,
+    #[serde(rename = "type")]
+    pub type_name: Style,
+    pub string: Style,
+    pub number: Style,
+    pub constant: Style,
+    pub heading: Style,
+    pub emphasis: Style,
+    pub key: Style,
+    pub variable: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            text: Style::default(),
+            welcome: Style::default(),
+            filler: Style::default(),
+            status_bar: Style {
+                reverse: true,
+                ..Style::default()
+            },
+            inactive_status_bar: Style {
+                reverse: true,
+                ..Style::fg(Color::Ansi(8))
+            },
+            separator: Style::fg(Color::Ansi(8)),
+            line_number: Style::fg(Color::Ansi(8)),
+            current_line_number: Style {
+                bold: true,
+                ..Style::default()
+            },
+            selection: Style {
+                reverse: true,
+                ..Style::default()
+            },
+            search_match: Style {
+                bg: Some(Color::Ansi(3)),
+                ..Style::fg(Color::Ansi(0))
+            },
+            selected_match: Style {
+                bg: Some(Color::Ansi(11)),
+                ..Style::fg(Color::Ansi(0))
+            },
+            comment: Style::fg(Color::Ansi(8)),
+            keyword: Style::fg(Color::Ansi(13)),
+            type_name: Style::fg(Color::Ansi(14)),
+            string: Style::fg(Color::Ansi(10)),
+            number: Style::fg(Color::Ansi(11)),
+            constant: Style::fg(Color::Ansi(11)),
+            heading: Style {
+                bold: true,
+                ..Style::fg(Color::Ansi(12))
+            },
+            emphasis: Style {
+                italic: true,
+                ..Style::default()
+            },
+            key: Style::fg(Color::Ansi(12)),
+            variable: Style::fg(Color::Ansi(9)),
+        }
+    }
+}
+
+impl Theme {
+    // name为None时使用默认主题, name是路径时直接读取这个文件
+    // 读取之后按照终端支持的颜色数量降级
+    pub fn load(name: Option<&str>, support: ColorSupport) -> Result<Self, String> {
+        let theme = match name {
+            None | Some("default") => Self::default(),
+            Some(name) => {
+                let path = Self::path(name).ok_or_else(|| format!("theme {name} not found"))?;
+                let contents = fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+                toml::from_str(&contents).map_err(|err| format!("{}: {}", path.display(), err.message()))?
+            }
+        };
+        Ok(theme.downgrade(support))
+    }
+
+    fn path(name: &str) -> Option<PathBuf> {
+        if name.contains('/') || name.ends_with(".toml") {
+            return Some(PathBuf::from(name));
+        }
+        Some(Config::dir()?.join("themes").join(format!("{name}.toml")))
+    }
+
+    fn downgrade(self, support: ColorSupport) -> Self {
+        let downgrade = |style: Style| style.downgrade(support);
+        Self {
+            text: downgrade(self.text),
+            welcome: downgrade(self.welcome),
+            filler: downgrade(self.filler),
+            status_bar: downgrade(self.status_bar),
+            inactive_status_bar: downgrade(self.inactive_status_bar),
+            separator: downgrade(self.separator),
+            line_number: downgrade(self.line_number),
+            current_line_number: downgrade(self.current_line_number),
+            selection: downgrade(self.selection),
+            search_match: downgrade(self.search_match),
+            selected_match: downgrade(self.selected_match),
+            comment: downgrade(self.comment),
+            keyword: downgrade(self.keyword),
+            type_name: downgrade(self.type_name),
+            string: downgrade(self.string),
+            number: downgrade(self.number),
+            constant: downgrade(self.constant),
+            heading: downgrade(self.heading),
+            emphasis: downgrade(self.emphasis),
+            key: downgrade(self.key),
+            variable: downgrade(self.variable),
+        }
+    }
+
+    // 正文中标注的部分, 叠加在正文的样式上
+    pub fn annotation(&self, annotation_type: Option<AnnotationType>) -> Style {
+        let Some(annotation_type) = annotation_type else {
+            return self.text;
+        };
+        let style = match annotation_type {
+            AnnotationType::Match => self.search_match,
+            AnnotationType::SelectedMatch => self.selected_match,
+            AnnotationType::Selection => self.selection,
+            AnnotationType::Comment => self.comment,
+            AnnotationType::Keyword => self.keyword,
+            AnnotationType::Type => self.type_name,
+            AnnotationType::String => self.string,
+            AnnotationType::Number => self.number,
+            AnnotationType::Constant => self.constant,
+            AnnotationType::Heading => self.heading,
+            AnnotationType::Emphasis => self.emphasis,
+            AnnotationType::Key => self.key,
+            AnnotationType::Variable => self.variable,
+        };
+        self.on_text(style)
+    }
+
+    // 正文区域里的其他内容(行号, ~, 欢迎信息)也以正文的样式为底色
+    pub fn on_text(&self, style: Style) -> Style {
+        self.text.patch(style)
+    }
+}
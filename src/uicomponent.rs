@@ -1,5 +1,5 @@
-use std::io::Error;
-use crate::terminal::Size;
+use crate::screen::Screen;
+use crate::terminal::{Position, Size};
 
 pub trait UIComponent {
     // 标记是否是要重绘
@@ -9,24 +9,18 @@ pub trait UIComponent {
 
     fn set_size(&mut self, size: Size);
 
-    fn draw(&mut self, origin_y: usize) -> Result<(), Error>;
+    // 以origin为左上角画到screen上, 之后由Editor把变化的部分输出到终端
+    fn draw(&mut self, origin: Position, screen: &mut Screen);
 
     fn resize(&mut self, size: Size) {
         self.set_size(size);
         self.mark_redraw(true);
     }
 
-    fn render(&mut self, origin_y: usize) {
+    fn render(&mut self, origin: Position, screen: &mut Screen) {
         if self.needs_redraw() {
-            match self.draw(origin_y) {
-                Ok(()) => self.mark_redraw(false),
-                Err(err) => {
-                    #[cfg(debug_assertions)]
-                    {
-                        panic!("Could not render component: {err:?}");
-                    }
-                }
-            }
+            self.draw(origin, screen);
+            self.mark_redraw(false);
         }
     }
 }
\ No newline at end of file
@@ -1,36 +1,94 @@
-use std::cmp::min;
+use std::cell::RefCell;
+use std::cmp::{min, Ordering};
 use std::io::Error;
+use std::mem;
+use std::rc::Rc;
 
-use crate::buffer::Buffer;
+use crate::annotation::{AnnotatedText, Annotation, AnnotationType};
+use regex::Regex;
+
+use crate::buffer::{Buffer, RegexMatch, TextChange};
+use crate::bufferlist::SavedBuffer;
+use crate::clipboard::Register;
 use crate::documentstatus::DocumentStatus;
 use crate::editor::{NAME, VERSION};
+use crate::encoding::Encoding;
+use crate::fileinfo::LineEnding;
+use crate::gutter::LineNumbers;
+use crate::indent::{self, Indentation};
 use crate::editorcommand::{Direction, EditorCommand};
-use crate::line::Line;
+use crate::line::{Line, TabStops};
 use crate::location::Location;
-use crate::terminal::{Position, Size, Terminal};
+use crate::style::StyledText;
+use crate::screen::Screen;
+use crate::terminal::{Position, Size};
+use crate::theme::Theme;
 use crate::uicomponent::UIComponent;
 
+// 滚轮每次滚动的行数
+const SCROLL_STEP: usize = 3;
+
+// 搜索开始前的状态, 取消搜索时要恢复
+struct SearchInfo {
+    prev_location: Location,
+    prev_scroll_offset: Position,
+    query: String,
+}
+
+// 逐个确认替换时的状态
+struct ReplaceInfo {
+    regex: Regex,
+    template: String,
+    // 正在等待确认的匹配
+    current: Option<RegexMatch>,
+    // 已经替换了多少处
+    count: usize,
+}
+
 pub struct View {
-    // 保存绘制的文本
-    buffer: Buffer,
+    // 保存绘制的文本, 可能同时显示在几个窗口里, 在一个窗口里的修改其他窗口马上就能看到
+    buffer: Rc<RefCell<Buffer>>,
     // 是否需要重绘
     needs_redraw: bool,
     // 窗口大小 The view always starts at `(0/0)`. The `size` property determines the visible area.
     size: Size,
-    margin_bottom: usize,
     text_location: Location,
+    // 选区的另一端, 选区是anchor和text_location之间的文本
+    selection_anchor: Option<Location>,
+    // 软换行模式下, 长的行会折成多个屏幕行显示, scroll_offset.row是屏幕行而不是行号
+    soft_wrap: bool,
+    line_numbers: LineNumbers,
+    tabs: TabStops,
+    indentation: Indentation,
+    theme: Theme,
     scroll_offset: Position,
+    // 软换行时最上面一个屏幕行是第几行的第几段, 画的时候记下来
+    // buffer在别的窗口里被修改之后, 用它重新算出scroll_offset.row
+    wrap_anchor: (usize, usize),
+    // 已经跟上了buffer的哪一次修改
+    revision: usize,
+    search_info: Option<SearchInfo>,
+    replace_info: Option<ReplaceInfo>,
 }
 
 impl Default for View {
     fn default() -> Self {
         Self {
-            buffer: Buffer::default(),
+            buffer: Rc::default(),
             needs_redraw: true,
-            size: Terminal::size().unwrap_or_default(),
-            margin_bottom: 0,
+            size: Size::default(),
             text_location: Location::default(),
+            selection_anchor: None,
+            soft_wrap: false,
+            line_numbers: LineNumbers::default(),
+            tabs: TabStops::default(),
+            indentation: Indentation::default(),
+            theme: Theme::default(),
             scroll_offset: Position::default(),
+            wrap_anchor: (0, 0),
+            revision: 0,
+            search_info: None,
+            replace_info: None,
         }
     }
 }
@@ -38,71 +96,753 @@ impl Default for View {
 impl View {
     pub fn get_status(&self) -> DocumentStatus {
         DocumentStatus {
-            total_lines: self.buffer.height(),
+            total_lines: self.buffer.borrow().height(),
             current_line_index: self.text_location.line_index,
-            filename: format!("{}", self.buffer.file_info),
-            is_modified: self.buffer.dirty,
+            filename: format!("{}", self.buffer.borrow().file_info),
+            is_modified: self.buffer.borrow().dirty,
+            line_ending: self.buffer.borrow().file_info.line_ending,
+            encoding: self.buffer.borrow().file_info.encoding,
+            is_read_only: self.buffer.borrow().file_info.read_only,
+            is_binary: self.buffer.borrow().file_info.binary,
+            language: self.buffer.borrow().language(),
         }
     }
 
 
-    pub fn load(&mut self, filename: &str) {
-        if let Ok(buffer) = Buffer::load(filename) {
-            self.buffer = buffer;
-            self.mark_redraw(true);
+    pub const fn buffer(&self) -> &Rc<RefCell<Buffer>> {
+        &self.buffer
+    }
+
+    // 分割窗口时新窗口的view: 显示同一个buffer, 光标和滚动位置也一样
+    pub fn split(&self) -> Self {
+        Self {
+            buffer: Rc::clone(&self.buffer),
+            text_location: self.text_location,
+            soft_wrap: self.soft_wrap,
+            line_numbers: self.line_numbers,
+            tabs: self.tabs,
+            indentation: self.indentation,
+            theme: self.theme.clone(),
+            scroll_offset: self.scroll_offset,
+            revision: self.buffer.borrow().revision(),
+            ..Self::default()
+        }
+    }
+
+    // 在这个view里做的修改, 光标已经在正确的位置上了
+    pub fn mark_synced(&mut self) {
+        self.revision = self.buffer.borrow().revision();
+    }
+
+    // buffer在别的窗口里被修改过, 光标, 选区和滚动位置跟着changes移动, 内容没变时什么也不做
+    // 重新读取文件之类没有修改记录的, 只保证它们不超出文本的范围
+    pub fn sync_with_buffer(&mut self, changes: &[TextChange]) {
+        let revision = self.buffer.borrow().revision();
+        if revision == self.revision {
+            return;
+        }
+        self.revision = revision;
+        let shift = |location| changes.iter().fold(location, |location, change| change.shift(location));
+        self.text_location = shift(self.text_location);
+        self.selection_anchor = self.selection_anchor.map(shift);
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.selection_anchor = self.selection_anchor.map(|anchor| self.clamp_location(anchor));
+        if self.soft_wrap {
+            let (line_index, sub_row) = self.wrap_anchor;
+            let top = shift(Location { line_index, grapheme_index: 0 }).line_index.min(self.buffer.borrow().height());
+            let rows = self
+                .buffer
+                .borrow()
+                .lines
+                .get(top)
+                .map_or(1, |line| line.wrapped_row_count(self.text_width(), self.tabs));
+            self.scroll_offset.row = self.visual_rows_before(top).saturating_add(sub_row.min(rows - 1));
+        } else {
+            let top = Location {
+                line_index: self.scroll_offset.row,
+                grapheme_index: 0,
+            };
+            self.scroll_offset.row = shift(top).line_index;
+        }
+        self.mark_redraw(true);
+    }
+
+    fn clamp_location(&self, location: Location) -> Location {
+        let buffer = self.buffer.borrow();
+        let line_index = location.line_index.min(buffer.height());
+        let grapheme_count = buffer.lines.get(line_index).map_or(0, Line::grapheme_count);
+        Location {
+            line_index,
+            grapheme_index: location.grapheme_index.min(grapheme_count),
         }
     }
 
+    // 换上另一个buffer, 恢复它保存的光标和滚动位置, 返回换下来的buffer和它的位置
+    // 选区, 搜索和替换都只属于换下来的buffer, 直接丢掉
+    pub fn swap_buffer(&mut self, saved: SavedBuffer) -> SavedBuffer {
+        self.selection_anchor = None;
+        self.search_info = None;
+        self.replace_info = None;
+        let previous = SavedBuffer {
+            buffer: mem::replace(&mut self.buffer, saved.buffer),
+            location: self.text_location,
+            scroll_offset: self.scroll_offset,
+        };
+        self.text_location = saved.location;
+        self.scroll_offset = saved.scroll_offset;
+        self.revision = self.buffer.borrow().revision();
+        // 离开之后窗口的大小可能变了
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
+        previous
+    }
+
+    // 用指定的编码重新打开当前文件, 有未保存的修改时拒绝
+    pub fn reload_with_encoding(&mut self, encoding: Encoding) -> Result<(), Error> {
+        let path = self.buffer.borrow().file_info.path.as_ref().and_then(|path| path.to_str()).map(String::from);
+        let Some(path) = path else {
+            return Err(Error::other("buffer has no file name"));
+        };
+        if self.buffer.borrow().dirty {
+            return Err(Error::other("buffer has unsaved changes"));
+        }
+        // 其他显示这个buffer的窗口也会看到重新打开之后的内容
+        self.buffer.borrow_mut().reload_with_encoding(&path, encoding)?;
+        self.move_to_location(self.text_location);
+        Ok(())
+    }
+
 
     pub fn handle_command(&mut self, cmd: EditorCommand) {
         match cmd {
-            EditorCommand::Move(dir) => self.move_text_location(dir),
-            EditorCommand::Insert(ch) => self.insert_char(ch),
-            EditorCommand::Backspace => self.backspace(),
-            EditorCommand::Delete => self.delete(),
+            EditorCommand::Move(dir) => {
+                self.clear_selection();
+                self.move_text_location(dir);
+            }
+            EditorCommand::Select(dir) => self.extend_selection(dir),
+            EditorCommand::Click(position) => self.click(position),
+            EditorCommand::DoubleClick(position) => self.select_word(position),
+            EditorCommand::Drag(position) => self.drag(position),
+            EditorCommand::Scroll(dir, _) => self.scroll(dir),
+            // 有选区的时候, 输入的内容会替换掉选中的文本
+            EditorCommand::Insert(ch) => {
+                if !self.replace_selection(&ch.to_string()) {
+                    self.insert_char(ch);
+                }
+            }
+            EditorCommand::PasteText(text) => self.insert_text(&text),
+            EditorCommand::Indent => {
+                if self.selection().is_some() {
+                    self.indent_lines(true);
+                } else {
+                    self.insert_tab();
+                }
+            }
+            EditorCommand::Dedent => self.indent_lines(false),
+            EditorCommand::Backspace => {
+                if !self.replace_selection("") {
+                    self.backspace();
+                }
+            }
+            EditorCommand::Delete => {
+                if !self.replace_selection("") {
+                    self.delete();
+                }
+            }
             EditorCommand::Enter => self.insert_newline(),
-            EditorCommand::Save => self.save(),
-            EditorCommand::Resize(_) => {}
-            EditorCommand::Quit => {}
+            EditorCommand::Undo => {
+                self.clear_selection();
+                self.undo();
+            }
+            EditorCommand::Redo => {
+                self.clear_selection();
+                self.redo();
+            }
+            EditorCommand::Resize(_)
+            | EditorCommand::Save
+            | EditorCommand::SaveAs
+            | EditorCommand::ToggleLineEnding
+            | EditorCommand::ReopenWithEncoding
+            | EditorCommand::ToggleSoftWrap
+            | EditorCommand::CycleLineNumbers
+            | EditorCommand::Quit
+            | EditorCommand::Search
+            | EditorCommand::Replace
+            | EditorCommand::Cut
+            | EditorCommand::Copy
+            | EditorCommand::Paste
+            | EditorCommand::Open
+            | EditorCommand::NextBuffer
+            | EditorCommand::PreviousBuffer
+            | EditorCommand::ListBuffers
+            | EditorCommand::CloseBuffer
+            | EditorCommand::SplitWindow(_)
+            | EditorCommand::CloseWindow
+            | EditorCommand::FocusWindow(_)
+            | EditorCommand::ResizeWindow(_)
+            | EditorCommand::Dismiss => {}
         }
     }
 
-    pub fn caret_position(&self) -> Position {
-        self.text_location_to_position()
-            .saturating_sub(self.scroll_offset)
+    // 选区的开头和结尾, 没有选中任何内容时返回None
+    fn selection(&self) -> Option<(Location, Location)> {
+        let anchor = self.selection_anchor?;
+        match anchor.cmp(&self.text_location) {
+            Ordering::Less => Some((anchor, self.text_location)),
+            Ordering::Greater => Some((self.text_location, anchor)),
+            Ordering::Equal => None,
+        }
     }
 
-    fn save(&mut self) {
-        let _ = self.buffer.save();
+    fn clear_selection(&mut self) {
+        if self.selection_anchor.take().is_some() {
+            self.mark_redraw(true);
+        }
     }
 
-    fn insert_newline(&mut self) {
-        self.buffer.insert_newline(self.text_location);
-        self.move_text_location(Direction::Right);
+    fn extend_selection(&mut self, dir: Direction) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.text_location);
+        }
+        self.move_text_location(dir);
+        self.mark_redraw(true);
+    }
+
+    // 点击时把光标放到点击的位置, 同时作为拖动选择的起点
+    fn click(&mut self, position: Position) {
+        let location = self.location_at(position);
+        self.selection_anchor = Some(location);
+        self.move_to_location(location);
+    }
+
+    fn drag(&mut self, position: Position) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.text_location);
+        }
+        let location = self.location_at(position);
+        self.move_to_location(location);
+    }
+
+    // 双击选中光标下的单词
+    fn select_word(&mut self, position: Position) {
+        let location = self.location_at(position);
+        let range = self.buffer.borrow().lines.get(location.line_index)
+            .map(|line| line.word_range_at(location.grapheme_index));
+        let Some(range) = range else {
+            self.click(position);
+            return;
+        };
+        self.selection_anchor = Some(Location {
+            line_index: location.line_index,
+            grapheme_index: range.start,
+        });
+        self.move_to_location(Location {
+            line_index: location.line_index,
+            grapheme_index: range.end,
+        });
+    }
+
+    // 滚轮只滚动画面, 光标留在原处, 可能会被滚出屏幕
+    fn scroll(&mut self, dir: Direction) {
+        let max_row = self
+            .visual_rows_before(self.buffer.borrow().height())
+            .saturating_sub(self.size.height.saturating_sub(1));
+        let row = match dir {
+            Direction::Up => self.scroll_offset.row.saturating_sub(SCROLL_STEP),
+            Direction::Down => self.scroll_offset.row.saturating_add(SCROLL_STEP).min(max_row),
+            _ => return,
+        };
+        if row != self.scroll_offset.row {
+            self.scroll_offset.row = row;
+            self.mark_redraw(true);
+        }
+    }
+
+    // 屏幕(view内)上的位置对应文本中的哪个位置
+    fn location_at(&self, position: Position) -> Location {
+        self.position_to_location(Position {
+            row: position.row.saturating_add(self.scroll_offset.row),
+            // 点在行号上时当成点在行首
+            col: position
+                .col
+                .saturating_sub(self.gutter_width())
+                .saturating_add(self.scroll_offset.col),
+        })
+    }
+
+    // 用text替换选中的文本, 没有选区时返回false
+    fn replace_selection(&mut self, text: &str) -> bool {
+        let Some((start, end)) = self.selection() else {
+            self.selection_anchor = None;
+            return false;
+        };
+        self.selection_anchor = None;
+        let location = self.buffer.borrow_mut().replace(start, end, text, self.text_location);
+        self.move_to_location(location);
+        true
+    }
+
+    // 复制选中的文本, 没有选区时复制光标所在的整行
+    pub fn copy(&self) -> Option<Register> {
+        if let Some((start, end)) = self.selection() {
+            return Some(Register {
+                text: self.buffer.borrow().text_range(start, end),
+                linewise: false,
+            });
+        }
+        self.buffer.borrow().lines.get(self.text_location.line_index).map(|line| Register {
+            text: line.to_string(),
+            linewise: true,
+        })
+    }
+
+    // 剪切选中的文本, 没有选区时剪切光标所在的整行
+    pub fn cut(&mut self) -> Option<Register> {
+        let register = self.copy()?;
+        if register.linewise {
+            let line_index = self.text_location.line_index;
+            let start = Location {
+                line_index,
+                grapheme_index: 0,
+            };
+            let end = Location {
+                line_index: line_index.saturating_add(1),
+                grapheme_index: 0,
+            };
+            self.buffer.borrow_mut().replace(start, end, "", self.text_location);
+            self.move_to_location(start);
+        } else {
+            self.replace_selection("");
+        }
+        Some(register)
+    }
+
+    // 整行的内容插到当前行的上面, 其他的插到光标处(替换掉选区)
+    pub fn paste(&mut self, register: &Register) {
+        if register.linewise {
+            self.clear_selection();
+            let start = Location {
+                line_index: self.text_location.line_index,
+                grapheme_index: 0,
+            };
+            self.buffer.borrow_mut().replace(start, start, &format!("{}\n", register.text), self.text_location);
+            self.move_to_location(start);
+        } else {
+            self.insert_text(&register.text);
+        }
+    }
+
+    // 一次性插入一整段文本(替换掉选区), 作为一次编辑撤销
+    fn insert_text(&mut self, text: &str) {
+        // 统一换行符, 粘贴进来的可能是CRLF或者CR
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if !self.replace_selection(&text) {
+            let location = self.buffer.borrow_mut().replace(self.text_location, self.text_location, &text, self.text_location);
+            self.move_to_location(location);
+        }
+    }
+
+    pub fn enter_search(&mut self) {
+        self.clear_selection();
+        self.search_info = Some(SearchInfo {
+            prev_location: self.text_location,
+            prev_scroll_offset: self.scroll_offset,
+            query: String::new(),
+        });
+    }
+
+    // 确认搜索, 光标停在当前的匹配上
+    pub fn exit_search(&mut self) {
+        self.search_info = None;
+        self.mark_redraw(true);
+    }
+
+    // 取消搜索, 回到搜索开始前的位置
+    pub fn dismiss_search(&mut self) {
+        if let Some(info) = self.search_info.take() {
+            self.text_location = info.prev_location;
+            self.scroll_offset = info.prev_scroll_offset;
+            self.scroll_location_into_view();
+            self.mark_redraw(true);
+        }
+    }
+
+    // 输入框里的内容变化时调用, 从搜索开始的位置重新查找
+    pub fn search(&mut self, query: &str) {
+        if let Some(info) = self.search_info.as_mut() {
+            info.query = query.to_string();
+            let from = info.prev_location;
+            let found = self.buffer.borrow().search_forward(query, from);
+            if let Some(location) = found {
+                self.move_to_location(location);
+            } else {
+                self.mark_redraw(true);
+            }
+        }
+    }
+
+    pub fn search_next(&mut self) {
+        let Some(query) = self.search_info.as_ref().map(|info| info.query.clone()) else {
+            return;
+        };
+        // 跳过当前的匹配
+        let from = Location {
+            line_index: self.text_location.line_index,
+            grapheme_index: self.text_location.grapheme_index.saturating_add(1),
+        };
+        let found = self.buffer.borrow().search_forward(&query, from);
+        if let Some(location) = found {
+            self.move_to_location(location);
+        }
+    }
+
+    pub fn search_prev(&mut self) {
+        let Some(query) = self.search_info.as_ref().map(|info| info.query.clone()) else {
+            return;
+        };
+        let found = self.buffer.borrow().search_backward(&query, self.text_location);
+        if let Some(location) = found {
+            self.move_to_location(location);
+        }
+    }
+
+    // 从文件开头开始查找要替换的内容, 返回是否找到了匹配
+    pub fn enter_replace(&mut self, regex: Regex, template: &str) -> bool {
+        self.clear_selection();
+        self.buffer.borrow_mut().begin_transaction();
+        self.replace_info = Some(ReplaceInfo {
+            regex,
+            template: template.to_string(),
+            current: None,
+            count: 0,
+        });
+        self.find_next_replacement(Location::default());
+        self.replace_info
+            .as_ref()
+            .is_some_and(|info| info.current.is_some())
+    }
+
+    // 结束替换, 所有替换可以一次undo, 返回替换的次数
+    pub fn exit_replace(&mut self) -> usize {
+        self.buffer.borrow_mut().end_transaction();
+        self.mark_redraw(true);
+        self.replace_info.take().map_or(0, |info| info.count)
+    }
+
+    // 替换当前的匹配并跳到下一个, 返回是否还有匹配
+    pub fn replace_current(&mut self) -> bool {
+        let Some(found) = self.replace_info.as_mut().and_then(|info| info.current.take()) else {
+            return false;
+        };
+        let end = self.buffer.borrow_mut().replace(found.start, found.end, &found.replacement, self.text_location);
+        if let Some(info) = self.replace_info.as_mut() {
+            info.count += 1;
+        }
+        self.find_next_replacement(self.next_search_start(found.start, end));
+        self.has_replacement()
+    }
+
+    // 跳过当前的匹配, 返回是否还有匹配
+    pub fn skip_current(&mut self) -> bool {
+        let Some(found) = self.replace_info.as_mut().and_then(|info| info.current.take()) else {
+            return false;
+        };
+        self.find_next_replacement(self.next_search_start(found.start, found.end));
+        self.has_replacement()
+    }
+
+    // 替换剩下所有的匹配
+    pub fn replace_all(&mut self) {
+        while self.replace_current() {}
+    }
+
+    fn has_replacement(&self) -> bool {
+        self.replace_info
+            .as_ref()
+            .is_some_and(|info| info.current.is_some())
+    }
+
+    // 空的匹配要往后挪一个grapheme, 否则会一直匹配到同一个位置
+    fn next_search_start(&self, start: Location, end: Location) -> Location {
+        if start != end {
+            return end;
+        }
+        let line_width = self.buffer.borrow().lines.get(end.line_index).map_or(0, Line::grapheme_count);
+        if end.grapheme_index < line_width {
+            Location {
+                line_index: end.line_index,
+                grapheme_index: end.grapheme_index.saturating_add(1),
+            }
+        } else {
+            Location {
+                line_index: end.line_index.saturating_add(1),
+                grapheme_index: 0,
+            }
+        }
+    }
+
+    fn find_next_replacement(&mut self, from: Location) {
+        let Some(info) = self.replace_info.as_mut() else {
+            return;
+        };
+        info.current = self.buffer.borrow().find_regex(&info.regex, &info.template, from);
+        if let Some(location) = info.current.as_ref().map(|found| found.start) {
+            self.move_to_location(location);
+        }
+        self.mark_redraw(true);
+    }
+
+    // 光标被滚轮滚出屏幕时返回None
+    pub fn caret_position(&self) -> Option<Position> {
+        let mut position = self.text_location_to_position();
+        if self.soft_wrap {
+            // 正好占满一整行时, 行尾的光标放在最后一列
+            position.col = position.col.min(self.text_width().saturating_sub(1));
+        }
+        let height = self.size.height;
+        let visible_rows = self.scroll_offset.row..self.scroll_offset.row.saturating_add(height);
+        let visible_cols = self.scroll_offset.col..self.scroll_offset.col.saturating_add(self.text_width());
+        if !visible_rows.contains(&position.row) || !visible_cols.contains(&position.col) {
+            return None;
+        }
+        let Position { row, col } = position.saturating_sub(self.scroll_offset);
+        Some(Position {
+            row,
+            col: col.saturating_add(self.gutter_width()),
+        })
+    }
+
+    // 切换行号的显示方式, 返回切换之后的方式
+    pub fn cycle_line_numbers(&mut self) -> LineNumbers {
+        self.set_line_numbers(self.line_numbers.next());
+        self.line_numbers
+    }
+
+    pub fn set_line_numbers(&mut self, line_numbers: LineNumbers) {
+        self.line_numbers = line_numbers;
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
+    }
+
+    pub fn set_tabs(&mut self, tabs: TabStops) {
+        self.tabs = tabs;
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
+    }
+
+    pub fn set_indentation(&mut self, indentation: Indentation) {
+        self.indentation = indentation;
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.mark_redraw(true);
+    }
+
+    fn gutter_width(&self) -> usize {
+        let width = self.line_numbers.width(self.buffer.borrow().height());
+        // 窗口太窄时不显示行号
+        if width < self.size.width {
+            width
+        } else {
+            0
+        }
+    }
+
+    // 去掉行号栏之后, 用来显示正文的宽度
+    fn text_width(&self) -> usize {
+        self.size.width.saturating_sub(self.gutter_width())
+    }
+
+    // 切换软换行, 返回切换之后是否开启
+    pub fn toggle_soft_wrap(&mut self) -> bool {
+        // 切换前后保持屏幕最上面的是同一行
+        let top_line = self.location_at(Position::default()).line_index;
+        self.soft_wrap = !self.soft_wrap;
+        self.scroll_offset = Position {
+            row: self.visual_rows_before(top_line),
+            col: 0,
+        };
+        self.scroll_location_into_view();
         self.mark_redraw(true);
+        self.soft_wrap
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.buffer.borrow().is_read_only()
+    }
+
+    // 没有文件名的buffer只能另存为
+    pub fn has_file_name(&self) -> bool {
+        self.buffer.borrow().file_info.path.is_some()
+    }
+
+    // 在LF和CRLF之间切换, 返回切换之后的换行符
+    pub fn toggle_line_ending(&mut self) -> LineEnding {
+        let line_ending = self.buffer.borrow().file_info.line_ending.toggle();
+        self.buffer.borrow_mut().set_line_ending(line_ending);
+        line_ending
+    }
+
+    pub fn save(&mut self) -> Result<(), Error> {
+        self.buffer.borrow_mut().save()
+    }
+
+    pub fn save_as(&mut self, filename: &str) -> Result<(), Error> {
+        self.buffer.borrow_mut().save_as(filename)
+    }
+
+    fn undo(&mut self) {
+        let location = self.buffer.borrow_mut().undo();
+        if let Some(location) = location {
+            self.move_to_location(location);
+        }
+    }
+
+    fn redo(&mut self) {
+        let location = self.buffer.borrow_mut().redo();
+        if let Some(location) = location {
+            self.move_to_location(location);
+        }
+    }
+
+    // 跳到指定位置, 并保证它是合法的
+    fn move_to_location(&mut self, location: Location) {
+        self.text_location = location;
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_location_into_view();
+        self.mark_redraw(true);
+    }
+
+    // 新的一行沿用当前行开头的缩进
+    fn insert_newline(&mut self) {
+        let start = self.selection().map_or(self.text_location, |(start, _)| start);
+        let indent = self.buffer.borrow().lines.get(start.line_index).map_or_else(String::new, |line| {
+            let before = line.substr(0..start.grapheme_index);
+            indent::leading_whitespace(&before).to_string()
+        });
+        self.insert_text(&format!("\n{indent}"));
+    }
+
+    fn insert_tab(&mut self) {
+        let col = self
+            .buffer
+            .borrow()
+            .lines
+            .get(self.text_location.line_index)
+            .map_or(0, |line| line.width_until(self.text_location.grapheme_index, self.tabs));
+        self.insert_text(&self.indentation.tab_at(col));
+    }
+
+    // 缩进或取消缩进选中的每一行(没有选区时是当前行), 作为一次编辑撤销
+    fn indent_lines(&mut self, indent: bool) {
+        let (start, end) = self.selection().unwrap_or((self.text_location, self.text_location));
+        // 选区结束在行首时, 那一行不算被选中
+        let last_line = if end.line_index > start.line_index && end.grapheme_index == 0 {
+            end.line_index - 1
+        } else {
+            end.line_index
+        };
+        let unit = self.indentation.unit();
+        self.buffer.borrow_mut().begin_transaction();
+        for line_index in start.line_index..=last_line {
+            let Some((is_empty, dedent_len)) = self.buffer.borrow().lines.get(line_index)
+                .map(|line| (line.grapheme_count() == 0, self.indentation.dedent_len(&line.to_string())))
+            else {
+                break;
+            };
+            let line_start = Location {
+                line_index,
+                grapheme_index: 0,
+            };
+            if indent {
+                // 空行不需要缩进
+                if is_empty {
+                    continue;
+                }
+                self.buffer.borrow_mut().replace(line_start, line_start, &unit, self.text_location);
+                self.shift_line_locations(line_index, unit.len(), 0);
+            } else {
+                let count = dedent_len;
+                if count == 0 {
+                    continue;
+                }
+                let indent_end = Location {
+                    line_index,
+                    grapheme_index: count,
+                };
+                self.buffer.borrow_mut().replace(line_start, indent_end, "", self.text_location);
+                self.shift_line_locations(line_index, 0, count);
+            }
+        }
+        self.buffer.borrow_mut().end_transaction();
+        self.move_to_location(self.text_location);
+    }
+
+    // 缩进改变之后, 让选区两端跟着这一行的文本一起移动
+    fn shift_line_locations(&mut self, line_index: usize, added: usize, removed: usize) {
+        let locations = self
+            .selection_anchor
+            .iter_mut()
+            .chain(std::iter::once(&mut self.text_location));
+        for location in locations {
+            // 在行首的留在行首, 这样整行仍然是选中的
+            if location.line_index == line_index && location.grapheme_index > 0 {
+                location.grapheme_index = location.grapheme_index.saturating_sub(removed).saturating_add(added);
+            }
+        }
     }
 
     fn backspace(&mut self) {
+        let Location {
+            line_index,
+            grapheme_index,
+        } = self.text_location;
+        // 光标前面全是空格时, 一次删掉一级缩进
+        let before = self
+            .buffer
+            .borrow()
+            .lines
+            .get(line_index)
+            .map_or_else(String::new, |line| line.substr(0..grapheme_index));
+        if !before.is_empty() && before.chars().all(|ch| ch == ' ') {
+            let start = Location {
+                line_index,
+                grapheme_index: grapheme_index - self.indentation.dedent_len(&before),
+            };
+            let location = self.buffer.borrow_mut().replace(start, self.text_location, "", self.text_location);
+            self.move_to_location(location);
+            return;
+        }
         if self.text_location.line_index != 0 || self.text_location.grapheme_index != 0 {
+            // undo之后光标要回到删除前的位置, 也就是被删掉的字符后面
+            let caret = self.text_location;
             self.move_text_location(Direction::Left);
-            self.delete();
+            self.buffer.borrow_mut().delete(self.text_location, caret);
+            self.mark_redraw(true);
         }
     }
 
     fn delete(&mut self) {
-        self.buffer.delete(self.text_location);
+        self.buffer.borrow_mut().delete(self.text_location, self.text_location);
         self.mark_redraw(true);
     }
 
     fn insert_char(&mut self, ch: char) {
         let old_len = self
             .buffer
+            .borrow()
             .lines
             .get(self.text_location.line_index)
             .map_or(0, Line::grapheme_count);
-        self.buffer.insert_char(ch, self.text_location);
+        self.buffer.borrow_mut().insert_char(ch, self.text_location);
         let new_len = self
             .buffer
+            .borrow()
             .lines
             .get(self.text_location.line_index)
             .map_or(0, Line::grapheme_count);
@@ -116,6 +856,7 @@ impl View {
 
     fn move_text_location(&mut self, dir: Direction) {
         let Size { height, .. } = self.size;
+        let line_index = self.text_location.line_index;
 
         // This match moves the position, but does not check for all boundaries.
         // The final boundarline checking happens after the match statement.
@@ -130,24 +871,47 @@ impl View {
             Direction::End => self.move_to_end_of_line(),
         }
 
+        // 换了行之后行号栏要更新当前行的高亮和相对行号
+        if self.line_numbers != LineNumbers::Off && self.text_location.line_index != line_index {
+            self.mark_redraw(true);
+        }
         self.scroll_location_into_view();
     }
 
     // 上移, 注意光标位置
     fn move_up(&mut self, step: usize) {
+        if self.soft_wrap {
+            self.move_wrapped(step, true);
+            return;
+        }
         self.text_location.line_index = self.text_location.line_index.saturating_sub(step);
         self.snap_to_valid_grapheme();
     }
 
     fn move_down(&mut self, step: usize) {
+        if self.soft_wrap {
+            self.move_wrapped(step, false);
+            return;
+        }
         self.text_location.line_index = self.text_location.line_index.saturating_add(step);
         self.snap_to_valid_grapheme();
         self.snap_to_valid_line();
     }
 
+    // 软换行时按屏幕行上下移动, 尽量保持在同一列
+    fn move_wrapped(&mut self, step: usize, up: bool) {
+        let Position { row, col } = self.text_location_to_position();
+        let row = if up {
+            row.saturating_sub(step)
+        } else {
+            row.saturating_add(step)
+        };
+        self.text_location = self.position_to_location(Position { row, col });
+    }
+
     fn move_right(&mut self) {
         // 光标所在行的文本
-        let line_width = self.buffer.lines
+        let line_width = self.buffer.borrow().lines
             .get(self.text_location.line_index)
             .map_or(0, Line::grapheme_count);
 
@@ -169,7 +933,7 @@ impl View {
     }
 
     fn move_to_end_of_line(&mut self) {
-        self.text_location.grapheme_index = self.buffer.lines
+        self.text_location.grapheme_index = self.buffer.borrow().lines
             .get(self.text_location.line_index)
             .map_or(0, Line::grapheme_count);
     }
@@ -182,7 +946,7 @@ impl View {
     // 不触发滚动
     fn snap_to_valid_grapheme(&mut self) {
         self.text_location.grapheme_index =
-            self.buffer.lines.get(self.text_location.line_index)
+            self.buffer.borrow().lines.get(self.text_location.line_index)
                 .map_or(0, |line| {
                     // 从长的行移到短的行, 要保证光标不能超出较短行的末尾
                     min(line.grapheme_count(), self.text_location.grapheme_index)
@@ -192,19 +956,100 @@ impl View {
     // 确保line_index指向正确的line
     // 不触发滚动
     fn snap_to_valid_line(&mut self) {
-        self.text_location.line_index = min(self.text_location.line_index, self.buffer.height())
+        self.text_location.line_index = min(self.text_location.line_index, self.buffer.borrow().height())
     }
 
+    // 软换行时row是从文件开头算起的屏幕行
     fn text_location_to_position(&self) -> Position {
-        let row = self.text_location.line_index;
-        let col = self.buffer.lines.get(row).map_or(0, |line| {
-            line.width_until(self.text_location.grapheme_index)
-        });
-        Position { col, row }
+        let Location { line_index, grapheme_index } = self.text_location;
+        let buffer = self.buffer.borrow();
+        let Some(line) = buffer.lines.get(line_index) else {
+            return Position {
+                col: 0,
+                row: self.visual_rows_before(line_index),
+            };
+        };
+        if !self.soft_wrap {
+            return Position {
+                col: line.width_until(grapheme_index, self.tabs),
+                row: line_index,
+            };
+        }
+        let rows = line.wrap(self.text_width(), self.tabs);
+        let sub_row = rows
+            .iter()
+            .rposition(|range| range.start <= grapheme_index)
+            .unwrap_or(0);
+        Position {
+            col: line
+                .width_until(grapheme_index, self.tabs)
+                .saturating_sub(line.width_until(rows[sub_row].start, self.tabs)),
+            row: self.visual_rows_before(line_index).saturating_add(sub_row),
+        }
+    }
+
+    // text_location_to_position的逆运算, 超出文件末尾时返回最后的空行
+    fn position_to_location(&self, position: Position) -> Location {
+        if !self.soft_wrap {
+            let line_index = position.row.min(self.buffer.borrow().height());
+            let grapheme_index = self
+                .buffer
+                .borrow()
+                .lines
+                .get(line_index)
+                .map_or(0, |line| line.grapheme_index_at(position.col, self.tabs));
+            return Location {
+                line_index,
+                grapheme_index,
+            };
+        }
+        let mut first_row: usize = 0;
+        for (line_index, line) in self.buffer.borrow().lines.iter().enumerate() {
+            // 前面的行只需要知道占几个屏幕行, 用缓存的结果跳过去
+            let row_count = line.wrapped_row_count(self.text_width(), self.tabs);
+            if position.row >= first_row.saturating_add(row_count) {
+                first_row = first_row.saturating_add(row_count);
+                continue;
+            }
+            let rows = line.wrap(self.text_width(), self.tabs);
+            let sub_row = position.row.saturating_sub(first_row);
+            if let Some(range) = rows.get(sub_row) {
+                let col = line.width_until(range.start, self.tabs).saturating_add(position.col);
+                // 除了最后一段, 光标不能放在段尾, 否则会显示在下一段的开头
+                let end = if sub_row + 1 < rows.len() {
+                    range.end.saturating_sub(1)
+                } else {
+                    range.end
+                };
+                return Location {
+                    line_index,
+                    grapheme_index: line.grapheme_index_at(col, self.tabs).min(end).max(range.start),
+                };
+            }
+            first_row = first_row.saturating_add(rows.len());
+        }
+        Location {
+            line_index: self.buffer.borrow().height(),
+            grapheme_index: 0,
+        }
+    }
+
+    // line_index之前的行一共占多少个屏幕行
+    fn visual_rows_before(&self, line_index: usize) -> usize {
+        if !self.soft_wrap {
+            return line_index;
+        }
+        self.buffer
+            .borrow()
+            .lines
+            .iter()
+            .take(line_index)
+            .map(|line| line.wrapped_row_count(self.text_width(), self.tabs))
+            .sum()
     }
 
     fn scroll_vertically(&mut self, to: usize) {
-        let Size { height, width } = self.size;
+        let Size { height, .. } = self.size;
         let mut offset_changed = false;
         if to < self.scroll_offset.row {
             self.scroll_offset.row = to;
@@ -218,7 +1063,7 @@ impl View {
     }
 
     fn scroll_horizontally(&mut self, to: usize) {
-        let Size { height, width } = self.size;
+        let width = self.text_width();
         let mut offset_changed = false;
         if to < self.scroll_offset.col {
             self.scroll_offset.col = to;
@@ -236,28 +1081,136 @@ impl View {
         // 一个grapheme在屏幕上占据多个位置(列), 所以要转换为position再计算offset
         let Position { row, col } = self.text_location_to_position();
         self.scroll_vertically(row);
-        self.scroll_horizontally(col);
+        // 软换行时不需要横向滚动
+        if !self.soft_wrap {
+            self.scroll_horizontally(col);
+        }
+    }
+
+    // 在语法高亮的基础上, 搜索时把这一行中所有的匹配标出来, 光标所在的匹配用不同的颜色
+    fn build_annotations(&self, line_idx: usize, line: &Line) -> Vec<Annotation> {
+        let mut annotations = self.buffer.borrow().syntax_annotations(line_idx).to_vec();
+        // 替换时只标出正在等待确认的那个匹配
+        if let Some(info) = &self.replace_info {
+            if let Some(found) = info.current.as_ref().filter(|found| found.start.line_index == line_idx) {
+                annotations.push(Annotation {
+                    annotation_type: AnnotationType::SelectedMatch,
+                    range: found.start.grapheme_index..found.end.grapheme_index,
+                });
+            }
+            return annotations;
+        }
+        if let Some(info) = &self.search_info {
+            annotations.extend(self.build_match_annotations(line_idx, line, &info.query));
+        }
+        // 选区画在最上面
+        if let Some((start, end)) = self.selection() {
+            if (start.line_index..=end.line_index).contains(&line_idx) {
+                let from = if line_idx == start.line_index { start.grapheme_index } else { 0 };
+                let to = if line_idx == end.line_index {
+                    end.grapheme_index
+                } else {
+                    line.grapheme_count()
+                };
+                annotations.push(Annotation {
+                    annotation_type: AnnotationType::Selection,
+                    range: from..to,
+                });
+            }
+        }
+        annotations
     }
 
-    fn render_line(row: usize, text: &str) -> Result<(), Error> {
-        let result = Terminal::print_row(row, text);
-        debug_assert!(result.is_ok(), "Failed to render line");
-        result
+    fn build_match_annotations(&self, line_idx: usize, line: &Line, query: &str) -> Vec<Annotation> {
+        line.find_all(query)
+            .into_iter()
+            .map(|range| {
+                let annotation_type = if line_idx == self.text_location.line_index
+                    && range.start == self.text_location.grapheme_index
+                {
+                    AnnotationType::SelectedMatch
+                } else {
+                    AnnotationType::Match
+                };
+                Annotation {
+                    annotation_type,
+                    range,
+                }
+            })
+            .collect()
     }
 
-    fn build_welcome_message(width: usize) -> String {
-        if width == 0 {
-            return " ".to_string();
+    // 软换行时一行可能占多个屏幕行, 从scroll_offset.row所在的屏幕行开始画
+    fn draw_wrapped(&mut self, origin: Position, screen: &mut Screen) {
+        let Size { height, .. } = self.size;
+        let origin_y = origin.row;
+        let gutter_width = self.gutter_width();
+        let end_y = origin_y.saturating_add(height);
+        let top_third = origin_y.saturating_add(height / 3);
+        let mut current_row = origin_y;
+        let mut visual_row = 0;
+        for (line_idx, line) in self.buffer.borrow().lines.iter().enumerate() {
+            if current_row >= end_y {
+                break;
+            }
+            // 滚出屏幕上方的行不用真的分段
+            let row_count = line.wrapped_row_count(self.text_width(), self.tabs);
+            let skip = self.scroll_offset.row.saturating_sub(visual_row);
+            visual_row = visual_row.saturating_add(row_count);
+            if skip >= row_count {
+                continue;
+            }
+            if current_row == origin_y {
+                self.wrap_anchor = (line_idx, skip);
+            }
+            let rows = line.wrap(self.text_width(), self.tabs);
+            let annotations = self.build_annotations(line_idx, line);
+            for (sub_row, range) in rows.into_iter().enumerate().skip(skip).take(end_y.saturating_sub(current_row)) {
+                // 只在每行的第一段显示行号
+                let number = (sub_row == 0).then_some(line_idx);
+                let gutter = self.line_numbers.render(number, self.text_location.line_index, gutter_width, &self.theme);
+                let cols = line.width_until(range.start, self.tabs)..line.width_until(range.end, self.tabs);
+                let text = line.get_annotated_visible_graphemes(cols, &annotations, self.tabs);
+                self.render_styled_line(screen, Position { row: current_row, ..origin }, gutter, text);
+                current_row = current_row.saturating_add(1);
+            }
         }
-        let welcome_message = format!("{NAME} editor -- version {VERSION}");
-        let len = welcome_message.len();
+        for current_row in current_row..end_y {
+            let show_welcome = current_row == top_third && self.buffer.borrow().is_empty();
+            self.render_filler(screen, Position { row: current_row, ..origin }, show_welcome);
+        }
+    }
+
+    // 画在at开始的一行上, 占满view的宽度
+    fn render_styled_line(&self, screen: &mut Screen, at: Position, gutter: StyledText, text: Vec<AnnotatedText>) {
+        let mut segments = vec![gutter];
+        segments.extend(
+            text.into_iter()
+                .map(|segment| StyledText::new(segment.text, self.theme.annotation(segment.annotation_type))),
+        );
+        screen.print_styled(at, self.size.width, self.theme.text, &segments);
+    }
 
+    // 文件末尾之后的行只显示 ~, 空文件在屏幕的三分之一处显示欢迎信息
+    fn render_filler(&self, screen: &mut Screen, at: Position, show_welcome: bool) {
+        let mut segments = vec![StyledText::new("~", self.theme.on_text(self.theme.filler))];
+        if show_welcome {
+            if let Some(message) = Self::build_welcome_message(self.size.width) {
+                segments.push(StyledText::new(message, self.theme.on_text(self.theme.welcome)));
+            }
+        }
+        screen.print_styled(at, self.size.width, self.theme.text, &segments);
+    }
+
+    // 欢迎信息放在 ~ 之后剩下的宽度中间
+    fn build_welcome_message(width: usize) -> Option<String> {
+        let welcome_message = format!("{NAME} editor -- version {VERSION}");
         let remaining_width = width.saturating_sub(1);
         // hide the welcome message if it doesn't fit entirely.
-        if remaining_width < len {
-            return "~".to_string();
+        if remaining_width < welcome_message.len() {
+            return None;
         }
-        format!("{:<1}{:^remaining_width$}", "~", welcome_message)
+        Some(format!("{welcome_message:^remaining_width$}"))
     }
 }
 
@@ -275,12 +1228,18 @@ impl UIComponent for View {
         self.scroll_location_into_view();
     }
 
-    fn draw(&mut self, origin_y: usize) -> Result<(), Error> {
+    fn draw(&mut self, origin: Position, screen: &mut Screen) {
+        // 每个缓冲区行至少占一个屏幕行, 所以屏幕上最多只会出现到这一行
+        self.buffer.borrow_mut().update_highlighting(self.scroll_offset.row.saturating_add(self.size.height));
+        if self.soft_wrap {
+            return self.draw_wrapped(origin, screen);
+        }
+        let origin_y = origin.row;
         let Size { height, width } = self.size;
         let end_y = origin_y.saturating_add(height);
         // we allow this since we don't care if our welcome message is put _exactly_ in the top third.
         // it's allowed to be a bit too far up or down
-        let top_third = height / 3;
+        let top_third = origin_y.saturating_add(height / 3);
         let scroll_top = self.scroll_offset.row;
         for current_row in origin_y..end_y {
             // to get the correct line index, we have to take current_row (the absolute row on screen),
@@ -289,16 +1248,18 @@ impl UIComponent for View {
             let line_idx = current_row
                 .saturating_sub(origin_y)
                 .saturating_add(scroll_top);
-            if let Some(line) = self.buffer.lines.get(line_idx) {
+            if let Some(line) = self.buffer.borrow().lines.get(line_idx) {
+                let gutter_width = self.gutter_width();
+                let gutter = self.line_numbers.render(Some(line_idx), self.text_location.line_index, gutter_width, &self.theme);
                 let left = self.scroll_offset.col;
-                let right = self.scroll_offset.col.saturating_add(width);
-                Self::render_line(current_row, &line.get_visible_graphemes(left..right))?;
-            } else if current_row == top_third && self.buffer.is_empty() {
-                Self::render_line(current_row, &Self::build_welcome_message(width))?;
+                let right = self.scroll_offset.col.saturating_add(width.saturating_sub(gutter_width));
+                let annotations = self.build_annotations(line_idx, line);
+                let text = line.get_annotated_visible_graphemes(left..right, &annotations, self.tabs);
+                self.render_styled_line(screen, Position { row: current_row, ..origin }, gutter, text);
             } else {
-                Self::render_line(current_row, "~")?;
+                let show_welcome = current_row == top_third && self.buffer.borrow().is_empty();
+                self.render_filler(screen, Position { row: current_row, ..origin }, show_welcome);
             }
         }
-        Ok(())
     }
 }
\ No newline at end of file
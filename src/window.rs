@@ -0,0 +1,107 @@
+use crate::layout::{Rect, WindowId};
+use crate::screen::Screen;
+use crate::statusbar::StatusBar;
+use crate::terminal::{Position, Size};
+use crate::theme::Theme;
+use crate::uicomponent::UIComponent;
+use crate::view::View;
+
+// 布局中的一个窗口: 上面是view, 最下面一行是它自己的状态栏
+pub struct Window {
+    pub id: WindowId,
+    pub view: View,
+    status_bar: StatusBar,
+    // 在屏幕上的位置, 由Editor按照布局安排
+    area: Rect,
+}
+
+impl Window {
+    pub fn new(id: WindowId, view: View, theme: Theme) -> Self {
+        let mut status_bar = StatusBar::default();
+        status_bar.set_theme(theme);
+        Self {
+            id,
+            view,
+            status_bar,
+            area: Rect::default(),
+        }
+    }
+
+    pub const fn area(&self) -> Rect {
+        self.area
+    }
+
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+        self.resize(area.size);
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.view.set_theme(theme.clone());
+        self.status_bar.set_theme(theme);
+    }
+
+    pub fn update_status(&mut self, focused: bool) {
+        self.status_bar.update_status(self.view.get_status());
+        self.status_bar.set_focused(focused);
+    }
+
+    // 光标在屏幕上的位置, 被滚出窗口时返回None
+    pub fn caret_position(&self) -> Option<Position> {
+        let Position { row, col } = self.view.caret_position()?;
+        Some(Position {
+            row: row + self.area.origin.row,
+            col: col + self.area.origin.col,
+        })
+    }
+
+    // 屏幕上的位置转换成view里的位置, 不在view里时返回None
+    pub fn view_position(&self, position: Position) -> Option<Position> {
+        let view_area = Rect {
+            size: Size {
+                height: self.area.size.height.saturating_sub(1),
+                ..self.area.size
+            },
+            ..self.area
+        };
+        view_area.contains(position).then(|| position.saturating_sub(self.area.origin))
+    }
+}
+
+impl UIComponent for Window {
+    fn mark_redraw(&mut self, value: bool) {
+        self.view.mark_redraw(value);
+        self.status_bar.mark_redraw(value);
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.view.needs_redraw() || self.status_bar.needs_redraw()
+    }
+
+    fn set_size(&mut self, size: Size) {
+        // 空出最下面一行给状态栏
+        self.view.resize(Size {
+            height: size.height.saturating_sub(1),
+            width: size.width,
+        });
+        self.status_bar.resize(Size {
+            height: 1,
+            width: size.width,
+        });
+    }
+
+    // view和状态栏各自只在需要的时候重画
+    fn draw(&mut self, origin: Position, screen: &mut Screen) {
+        let height = self.area.size.height;
+        if height > 1 {
+            self.view.render(origin, screen);
+        }
+        if height > 0 {
+            let status_origin = Position {
+                row: origin.row + height - 1,
+                ..origin
+            };
+            self.status_bar.render(status_origin, screen);
+        }
+    }
+}